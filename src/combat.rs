@@ -0,0 +1,518 @@
+use super::components_bundles::*;
+use super::fighters_movement_map::*;
+use super::{FightersMovementAnimationIndicies, FLOOR_Z, ANIMATION_TIME};
+
+use bevy::prelude::*;
+use bevy::ecs::query::WorldQuery;
+
+//guard
+const CHIP_DAMAGE_RATIO : f32 = 0.1; //fraction of a blocked hit's damage that still hurts
+const BLOCKSTUN_RATIO : f32 = 0.5; //fraction of a hit's stun applied when it is blocked
+const GUARD_BREAK_STUN : f32 = 1.5;
+const GUARD_RECOVERY_RATE : f32 = 10.0; //guard regained per second while not docking
+
+//combo
+const COMBO_DAMAGE_SCALING : f32 = 0.85; //damage multiplier compounded for every hit after the first
+const MIN_COMBO_DAMAGE_SCALING : f32 = 0.3;
+
+//parry
+const PARRY_STAGGER : f32 = 0.5; //seconds the parried attacker can't be controlled
+
+//counter hits land during the startup of the defender's attack
+const COUNTER_HIT_DAMAGE_RATIO : f32 = 1.25;
+const COUNTER_HIT_STUN_RATIO : f32 = 1.5;
+
+//meter gained per point of damage
+const METER_PER_DAMAGE_DEALT : f32 = 1.0;
+const METER_PER_DAMAGE_TAKEN : f32 = 0.5;
+
+//hitstop, in animation frames
+const HITSTOP_BASE_FRAMES : f32 = 2.0;
+const HITSTOP_FRAMES_PER_DAMAGE : f32 = 0.3;
+
+//attack phase of the fighter's current movement node, None outside of attacks
+#[derive(Component, Default)]
+pub struct FighterPhase(pub Option<MovementPhase>);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HitKind {
+    Normal,
+    CounterHit, //the defender was in the startup of its attack
+    Punish, //the defender was in the recovery of its attack
+    Blocked,
+    Parried,
+    Throw,
+}
+
+//fired for every hit that connects, blocked or not
+#[derive(Event)]
+pub struct HitEvent {
+    pub attacker : Entity,
+    pub defender : Entity,
+    pub kind : HitKind,
+    pub damage : f32,
+    pub contact_point : [f32;3], //world x,y,z where the hit landed
+}
+
+//place the boxes of each fighter's current movement node and animation frame at its position and facing
+pub(crate) fn update_fighter_boxes(mut query: Query<(&Fighter,
+                                            &FighterMovementStack,
+                                            &FighterPosition,
+                                            &FacingEast,
+                                            &TextureAtlasSprite,
+                                            &mut FighterHitBox,
+                                            &mut FighterHurtBox,
+                                            &mut FighterPhase,
+                                            &FighterJuggle,)>,
+                                            figher_movement_map_collection: Res<FighterMovementMapCollection>,
+                                            fighters_movement_animation_indicies: Res<FightersMovementAnimationIndicies>,) {
+    for (fighter,
+        movement_stack,
+        position,
+        facing_east,
+        sprite,
+        mut hitbox,
+        mut hurtbox,
+        mut phase,
+        juggle) in query.iter_mut() {
+
+        let fighter_map = figher_movement_map_collection.0.get(fighter)
+            .expect("fighter does not exist in the movement graph");
+        if let Some(current_durative_movement) = movement_stack.last() {
+            let movement_node = fighter_map.get_node_by_movement(&current_durative_movement.value)
+                .expect("movement wasn't found in fighter_map");
+
+            let frame = fighters_movement_animation_indicies.0.get(fighter).unwrap()
+                                    .animation_frame(movement_node.sprite_name(), sprite.index);
+
+            hitbox.hitbox = movement_node.hit_box(frame).placed(position, facing_east.0);
+            hitbox.lane_tolerance = movement_node.lane_tolerance();
+            //the hit is the one of the box that is out
+            if let Some(framed_hit_box) = movement_node.framed_hit_box(frame) {
+                hitbox.damage = framed_hit_box.damage;
+                hitbox.knockback = framed_hit_box.knockback;
+                hitbox.stun = framed_hit_box.stun;
+            }
+            hurtbox.hitbox = movement_node.hurt_box(frame).placed(position, facing_east.0);
+            hurtbox.invunerable = movement_node.invunerable(frame);
+            hurtbox.armor = movement_node.armor(frame);
+            //juggled fighters out of juggle points fall through further hits
+            if juggle.hits >= JUGGLE_POINTS {
+                hurtbox.invunerable = true;
+            }
+            phase.0 = match movement_node {
+                FighterMovementNode::EventTriggered(node) => node.phase(frame),
+                _ => None,
+            };
+
+            //a swing lasts while its hit box is out
+            if hitbox.hitbox.is_empty() {
+                hitbox.landed = false;
+            }
+        }
+    }
+}
+
+#[derive(WorldQuery)]
+#[world_query(mutable)]
+pub(crate) struct Combatant {
+    pub(crate) entity : Entity,
+    pub(crate) fighter : &'static Fighter,
+    pub(crate) hitbox : &'static mut FighterHitBox,
+    pub(crate) hurtbox : &'static FighterHurtBox,
+    pub(crate) phase : &'static FighterPhase,
+    pub(crate) health : &'static mut FighterHealth,
+    pub(crate) combo : &'static mut FighterCombo,
+    pub(crate) juggle : &'static mut FighterJuggle,
+    pub(crate) guard : &'static mut FighterGuard,
+    pub(crate) position : &'static mut FighterPosition,
+    pub(crate) velocity : &'static mut FighterVelocity,
+    pub(crate) facing_east : &'static mut FacingEast,
+    pub(crate) stun : &'static mut FighterStun,
+    pub(crate) hitstop : &'static mut FighterHitstop,
+    pub(crate) movement_stack : &'static mut FighterMovementStack,
+    pub(crate) keytargetset_stack : &'static KeyTargetSetStack,
+    pub(crate) parry : &'static FighterParry,
+}
+
+//returns true if the attacker's hitbox landed on the defender's hurtbox
+pub(crate) fn try_hit(attacker_hitbox : &mut FighterHitBox, defender_hurtbox : &FighterHurtBox) -> bool {
+    if attacker_hitbox.landed || attacker_hitbox.hitbox.is_empty() || defender_hurtbox.hitbox.is_empty() {
+        return false
+    }
+    //hits pass through invunerable fighters, the swing may still land once they are vulnerable
+    if defender_hurtbox.invunerable {
+        return false
+    }
+    //like in belt-scrollers, attacks only connect with fighters close to their lane.
+    //boxes are centered on their fighters' lanes, so their depths are compared
+    if (attacker_hitbox.hitbox.center[2] - defender_hurtbox.hitbox.center[2]).abs() > attacker_hitbox.lane_tolerance {
+        return false
+    }
+    if !attacker_hitbox.hitbox.intersection(&defender_hurtbox.hitbox) {
+        return false
+    }
+    attacker_hitbox.landed = true;
+    true
+}
+
+//push a movement the defender didn't choose onto its stack
+pub(crate) fn force_movement(defender : &mut CombatantItem,
+                movement : FighterMovement,
+                defender_map : &FighterMovementMap) {
+    let node = defender_map.get_node_by_movement(&movement)
+        .expect("forced movement wasn't found in fighter_map");
+    defender.movement_stack.push(movement);
+    node.state_enter(&mut defender.position, &mut defender.velocity, &mut defender.facing_east, &defender_map.stats);
+}
+
+fn in_hitstun(defender : &CombatantItem) -> bool {
+    match defender.movement_stack.last() {
+        Some(durative_movement) => {
+            durative_movement.value == FighterMovement::HitStun
+                || durative_movement.value == FighterMovement::KnockedBack
+                || durative_movement.value == FighterMovement::Juggled
+                || durative_movement.value == FighterMovement::WallBounce
+                || durative_movement.value == FighterMovement::GroundBounce
+        },
+        None => false,
+    }
+}
+
+//docking or blockstunned defenders that face the attacker block
+fn is_blocking(attacker_x : f32, defender : &CombatantItem) -> bool {
+    let guarding = match defender.movement_stack.last() {
+        Some(durative_movement) => {
+            durative_movement.value == FighterMovement::Docking || durative_movement.value == FighterMovement::BlockStun
+        },
+        None => false,
+    };
+    let facing_attacker = defender.facing_east.0 == (attacker_x > defender.position.x);
+    guarding && facing_attacker
+}
+
+//docking defenders that face the attacker and pressed defend within their parry window parry
+fn is_parrying(attacker_x : f32, defender : &CombatantItem) -> bool {
+    let docking = match defender.movement_stack.last() {
+        Some(durative_movement) => durative_movement.value == FighterMovement::Docking,
+        None => false,
+    };
+    if !docking || !is_blocking(attacker_x, defender) {
+        return false
+    }
+    for timed_keyset in defender.keytargetset_stack.0.stack.iter().rev() {
+        if timed_keyset.duration > defender.parry.window {break};
+        if timed_keyset.value.contains(&KeyTarget::DefendJustPressed) {return true};
+    }
+    false
+}
+
+//force the defender into a hit reaction movement.
+//airborne or launched defenders are juggled, the rest are stunned in place. heavier defenders are knocked back less
+pub(crate) fn enter_hit_reaction(hit : &FighterHitBox,
+                    attacker_facing_east : bool,
+                    defender : &mut CombatantItem,
+                    defender_map : &FighterMovementMap) {
+    let reaction = if defender.position.z != FLOOR_Z || hit.knockback[1] > 0.0 {
+        defender.juggle.hits += 1;
+        FighterMovement::Juggled
+    } else {
+        FighterMovement::HitStun
+    };
+    force_movement(defender, reaction, defender_map);
+
+    let away = if attacker_facing_east {1.0} else {-1.0};
+    let weight = defender_map.stats.weight;
+    defender.velocity.x = away * hit.knockback[0] / weight;
+    defender.velocity.y = 0.0;
+    defender.velocity.z = hit.knockback[1] / weight;
+    defender.stun.0 = hit.stun;
+}
+
+//a blocked hit deals chip damage and drains the guard, pushing the defender back in blockstun.
+//when the guard empties the defender is left open in a guard break
+fn enter_block_reaction(hit : &FighterHitBox,
+                    attacker_facing_east : bool,
+                    defender : &mut CombatantItem,
+                    defender_map : &FighterMovementMap) {
+    defender.health.current = (defender.health.current - hit.damage * CHIP_DAMAGE_RATIO).max(0.0);
+    defender.guard.current = (defender.guard.current - hit.damage).max(0.0);
+
+    if defender.guard.current == 0.0 {
+        force_movement(defender, FighterMovement::GuardBreak, defender_map);
+        defender.stun.0 = GUARD_BREAK_STUN;
+        return
+    }
+
+    force_movement(defender, FighterMovement::BlockStun, defender_map);
+    let away = if attacker_facing_east {1.0} else {-1.0};
+    defender.velocity.x = away * hit.knockback[0] / defender_map.stats.weight;
+    defender.velocity.y = 0.0;
+    defender.stun.0 = hit.stun * BLOCKSTUN_RATIO;
+}
+
+//hits on a defender that is still in hitstun continue its combo, with decaying damage.
+//returns the damage dealt
+pub(crate) fn deal_combo_damage(damage : f32, defender : &mut CombatantItem) -> f32 {
+    if !in_hitstun(defender) {
+        defender.combo.hits = 0;
+        defender.combo.damage = 0.0;
+    }
+    let scaling = COMBO_DAMAGE_SCALING.powi(defender.combo.hits as i32).max(MIN_COMBO_DAMAGE_SCALING);
+    let damage = damage * scaling;
+    defender.combo.hits += 1;
+    defender.combo.damage += damage;
+
+    defender.health.current = (defender.health.current - damage).max(0.0);
+    damage
+}
+
+//apply a hit that connected with the defender, from an attacker at attacker_x facing attacker_facing_east.
+//returns the hitstop the attacker should freeze for, and the kind of the hit
+pub(crate) fn apply_hit(hit : &FighterHitBox,
+                        attacker : Entity,
+                        attacker_x : f32,
+                        attacker_facing_east : bool,
+                        defender : &mut CombatantItem,
+                        defender_map : &FighterMovementMap,
+                        hit_events : &mut EventWriter<HitEvent>) -> (f32, HitKind) {
+    //both sides freeze on impact, longer for heavier hits
+    let hitstop = (HITSTOP_BASE_FRAMES + HITSTOP_FRAMES_PER_DAMAGE * hit.damage) * ANIMATION_TIME;
+    defender.hitstop.0 = hitstop;
+    let contact_point = hit.hitbox.contact_point(&defender.hurtbox.hitbox);
+
+    //parried hits deal nothing, the defender is free to act right after the hitstop
+    if is_parrying(attacker_x, defender) {
+        hit_events.send(HitEvent {
+            attacker : attacker,
+            defender : defender.entity,
+            kind : HitKind::Parried,
+            damage : 0.0,
+            contact_point : contact_point,
+        });
+        info!("{} parried", defender.fighter);
+        return (hitstop, HitKind::Parried)
+    }
+
+    if is_blocking(attacker_x, defender) {
+        let health = defender.health.current;
+        enter_block_reaction(hit, attacker_facing_east, defender, defender_map);
+        hit_events.send(HitEvent {
+            attacker : attacker,
+            defender : defender.entity,
+            kind : HitKind::Blocked,
+            damage : health - defender.health.current,
+            contact_point : contact_point,
+        });
+        info!("{} blocked, guard left {}", defender.fighter, defender.guard.current);
+        return (hitstop, HitKind::Blocked)
+    }
+
+    //defenders caught in the middle of their own attack take more
+    let kind = match defender.phase.0 {
+        Some(MovementPhase::Startup) => HitKind::CounterHit,
+        Some(MovementPhase::Recovery) => HitKind::Punish,
+        _ => HitKind::Normal,
+    };
+    let (damage_ratio, stun_ratio) = if kind == HitKind::CounterHit {
+        (COUNTER_HIT_DAMAGE_RATIO, COUNTER_HIT_STUN_RATIO)
+    } else {
+        (1.0, 1.0)
+    };
+
+    let damage = deal_combo_damage(hit.damage * damage_ratio, defender);
+    //armored fighters take the damage but carry on with their movement
+    if !defender.hurtbox.armor {
+        enter_hit_reaction(hit, attacker_facing_east, defender, defender_map);
+        defender.stun.0 *= stun_ratio;
+    }
+    hit_events.send(HitEvent {
+        attacker : attacker,
+        defender : defender.entity,
+        kind : kind,
+        damage : damage,
+        contact_point : contact_point,
+    });
+    info!("{} was hit ({:?}), health left {}", defender.fighter, kind, defender.health.current);
+    (hitstop, kind)
+}
+
+fn resolve_hit(attacker : &mut CombatantItem,
+                defender : &mut CombatantItem,
+                figher_movement_map_collection : &FighterMovementMapCollection,
+                hit_events : &mut EventWriter<HitEvent>) {
+    if !try_hit(&mut attacker.hitbox, defender.hurtbox) {
+        return
+    }
+    let defender_map = figher_movement_map_collection.0.get(defender.fighter)
+        .expect("fighter does not exist in the movement graph");
+    let (hitstop, kind) = apply_hit(&attacker.hitbox,
+                                attacker.entity,
+                                attacker.position.x,
+                                attacker.facing_east.0,
+                                defender,
+                                defender_map,
+                                hit_events);
+    attacker.hitstop.0 = hitstop;
+
+    //a parried attacker staggers, leaving the defender with the advantage.
+    //airborne attackers fall out of the air instead
+    if kind == HitKind::Parried {
+        let attacker_map = figher_movement_map_collection.0.get(attacker.fighter)
+            .expect("fighter does not exist in the movement graph");
+        let stagger = if attacker.position.z == FLOOR_Z {
+            FighterMovement::Staggered
+        } else {
+            FighterMovement::KnockedBack
+        };
+        force_movement(attacker, stagger, attacker_map);
+        attacker.stun.0 = PARRY_STAGGER;
+    }
+}
+
+pub(crate) fn resolve_hits(mut query: Query<Combatant>,
+                            mut hit_events: EventWriter<HitEvent>,
+                            figher_movement_map_collection: Res<FighterMovementMapCollection>,) {
+    let mut combinations = query.iter_combinations_mut();
+    while let Some([mut fighter_a, mut fighter_b]) = combinations.fetch_next() {
+        resolve_hit(&mut fighter_a, &mut fighter_b, &figher_movement_map_collection, &mut hit_events);
+        resolve_hit(&mut fighter_b, &mut fighter_a, &figher_movement_map_collection, &mut hit_events);
+    }
+}
+
+//every juggle hit makes the juggled fighter fall faster until landing, also while
+//airborne in hitstun or any other movement the hit puts them in.
+//launched fighters are still on the floor through the hitstop, and bounced ones leave it rising,
+//so only fighters that came to rest on the floor end their juggle
+pub(crate) fn update_juggles(mut query: Query<(&Fighter,
+                                            &FighterPosition,
+                                            &FighterHitstop,
+                                            &mut FighterVelocity,
+                                            &mut FighterJuggle,)>,
+                            time: Res<Time>,
+                            figher_movement_map_collection: Res<FighterMovementMapCollection>,) {
+    for (fighter,
+        position,
+        hitstop,
+        mut velocity,
+        mut juggle) in query.iter_mut() {
+        if position.z == FLOOR_Z && velocity.z <= 0.0 {
+            juggle.hits = 0;
+            continue;
+        }
+        if hitstop.0 > 0.0 || juggle.hits == 0 {continue};
+        let fighter_map = figher_movement_map_collection.0.get(fighter)
+            .expect("fighter does not exist in the movement graph");
+        velocity.z += fighter_map.stats.gravity * JUGGLE_GRAVITY_SCALING * juggle.hits as f32 * time.delta_seconds();
+    }
+}
+
+//both sides of a hit build meter, the attacker more
+pub(crate) fn gain_meter(mut hit_events: EventReader<HitEvent>,
+                        mut query: Query<&mut FighterMeter>,) {
+    for event in hit_events.iter() {
+        if let Ok(mut meter) = query.get_mut(event.attacker) {
+            meter.current = (meter.current + event.damage * METER_PER_DAMAGE_DEALT).min(meter.max);
+        }
+        if let Ok(mut meter) = query.get_mut(event.defender) {
+            meter.current = (meter.current + event.damage * METER_PER_DAMAGE_TAKEN).min(meter.max);
+        }
+    }
+}
+
+//the guard only recovers while not blocking, in blockstun or broken
+pub(crate) fn recover_guards(mut query: Query<(&FighterMovementStack, &mut FighterGuard)>,
+                            time: Res<Time>,) {
+    for (movement_stack, mut guard) in query.iter_mut() {
+        if let Some(current_durative_movement) = movement_stack.last() {
+            if !matches!(current_durative_movement.value,
+                        FighterMovement::Docking | FighterMovement::BlockStun | FighterMovement::GuardBreak) {
+                guard.current = (guard.current + GUARD_RECOVERY_RATE * time.delta_seconds()).min(guard.max);
+            }
+        }
+    }
+}
+
+//combos end once the defender recovers
+pub(crate) fn reset_combos(mut query: Query<(&FighterMovementStack, &mut FighterCombo)>) {
+    for (movement_stack, mut combo) in query.iter_mut() {
+        if let Some(current_durative_movement) = movement_stack.last() {
+            if current_durative_movement.value == FighterMovement::Idle && combo.hits > 0 {
+                *combo = FighterCombo::default();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::movement_definitions::shipped_movement_maps;
+
+    fn spawn_combatant(world : &mut World, fighter : Fighter, x : f32, facing_east : bool) -> Entity {
+        world.spawn(((fighter,
+                    FighterHitBox::default(),
+                    FighterHurtBox::default(),
+                    FighterPhase::default(),
+                    FighterHealth{current : 100.0, max : 100.0},
+                    FighterCombo::default(),
+                    FighterJuggle::default(),
+                    FighterGuard{current : 50.0, max : 50.0},),
+                    (FighterPosition{x : x, y : 0.0, z : FLOOR_Z},
+                    FighterVelocity{x : 0.0, y : 0.0, z : 0.0},
+                    FacingEast(facing_east),
+                    FighterStun(0.0),
+                    FighterHitstop(0.0),
+                    FighterMovementStack::new(10),
+                    KeyTargetSetStack::new(10, 0.5),
+                    FighterParry{window : 0.1},))).id()
+    }
+
+    //swing the attacker's movement at the frame into the defender, wherever it stands
+    fn swing(world : &mut World, attacker : Entity, defender : Entity, movement : FighterMovement, frame : usize) {
+        let framed_hit_box = *world.resource::<FighterMovementMapCollection>().0.get(&Fighter::HAMAS).unwrap()
+            .get_node_by_movement(&movement).unwrap()
+            .framed_hit_box(frame).unwrap();
+        let position = world.get::<FighterPosition>(defender).unwrap();
+        let body = HitBox{center : [0.0, 0.0, 0.0], theta : 0.0, dx : 30.0, dy : 64.0, dz : 20.0}.placed(position, true);
+        world.get_mut::<FighterHurtBox>(defender).unwrap().hitbox = body;
+        *world.get_mut::<FighterHitBox>(attacker).unwrap() = FighterHitBox{
+            hitbox : body,
+            lane_tolerance : DEFAULT_LANE_TOLERANCE,
+            damage : framed_hit_box.damage,
+            knockback : framed_hit_box.knockback,
+            stun : framed_hit_box.stun,
+            landed : false,
+        };
+    }
+
+    #[test]
+    fn grounded_launcher_costs_a_juggle_point() {
+        let mut world = World::new();
+        world.insert_resource(shipped_movement_maps());
+        world.init_resource::<Time>();
+        world.init_resource::<Events<HitEvent>>();
+        let mut tick = Schedule::default();
+        tick.add_systems((update_juggles, resolve_hits).chain());
+        let attacker = spawn_combatant(&mut world, Fighter::HAMAS, 0.0, true);
+        let defender = spawn_combatant(&mut world, Fighter::IDF, 20.0, false);
+
+        swing(&mut world, attacker, defender, FighterMovement::Uppercut, 0);
+        tick.run(&mut world);
+        assert_eq!(world.get::<FighterMovementStack>(defender).unwrap().last().unwrap().value, FighterMovement::Juggled);
+        assert!(world.get::<FighterVelocity>(defender).unwrap().z > 0.0);
+
+        //the launched defender waits out the hitstop on the floor
+        assert!(world.get::<FighterHitstop>(defender).unwrap().0 > 0.0);
+        tick.run(&mut world);
+        world.get_mut::<FighterHitstop>(defender).unwrap().0 = 0.0;
+        tick.run(&mut world);
+        assert_eq!(world.get::<FighterJuggle>(defender).unwrap().hits, 1);
+
+        world.get_mut::<FighterPosition>(defender).unwrap().z = 40.0;
+        swing(&mut world, attacker, defender, FighterMovement::JumpAttack, 1);
+        tick.run(&mut world);
+        //the launcher and the air hit, out of JUGGLE_POINTS
+        assert_eq!(world.get::<FighterJuggle>(defender).unwrap().hits, 2);
+    }
+}
@@ -7,6 +7,7 @@ use std::collections::BTreeSet;
 use std::ops::Add;
 use std::fmt::Display;
 use std::fmt;
+use serde::Deserialize;
 
 
 #[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash, Display)]
@@ -21,6 +22,54 @@ pub struct FighterHealth{
     pub max: f32,
 }
 
+//consecutive hits taken while in hitstun, and their total damage
+#[derive(Component, Default)]
+pub struct FighterCombo{
+    pub hits: u32,
+    pub damage: f32,
+}
+
+//hits taken in the air since the fighter last stood on the floor
+#[derive(Component, Default)]
+pub struct FighterJuggle{
+    pub hits: u32,
+}
+
+//filled by dealing and taking damage, spent on meter moves. the bar is split into segments
+#[derive(Component)]
+pub struct FighterMeter{
+    pub current: f32,
+    pub max: f32,
+}
+
+//absorbs blocked hits, the guard breaks when it empties
+#[derive(Component)]
+pub struct FighterGuard{
+    pub current: f32,
+    pub max: f32,
+}
+
+//drains while running and dashing, recovers while walking or idle
+#[derive(Component)]
+pub struct FighterStamina{
+    pub current: f32,
+    pub max: f32,
+    pub drain_rate: f32, //per second
+    pub recovery_rate: f32, //per second
+}
+
+impl FighterStamina {
+    pub fn new(drain_rate : f32, recovery_rate : f32) -> Self {
+        Self{current : 100.0, max : 100.0, drain_rate, recovery_rate}
+    }
+}
+
+//seconds before a hit in which pressing defend while docking parries it
+#[derive(Component)]
+pub struct FighterParry{
+    pub window: f32,
+}
+
 #[derive(Component)]
 pub struct FighterPosition {
     pub x : f32, //right
@@ -34,6 +83,14 @@ impl From<&FighterPosition> for [f32;3]{
     }
 }
 
+//body space the fighter keeps the other fighters out of, centered at its position.
+//it only holds on the floor, so airborne fighters pass over each other
+#[derive(Component)]
+pub struct FighterPushBox {
+    pub dx : f32, //width
+    pub dy : f32, //depth
+}
+
 #[derive(Component)]
 pub struct FighterVelocity {
     pub x : f32,
@@ -44,8 +101,17 @@ pub struct FighterVelocity {
 #[derive(Component)]
 pub struct FacingEast(pub bool);
 
+//seconds left until the fighter can be controlled again
+#[derive(Component)]
+pub struct FighterStun(pub f32);
+
+//seconds left of the freeze that follows a hit, the fighter's state and animation are paused
+#[derive(Component)]
+pub struct FighterHitstop(pub f32);
+
+#[derive(Clone, Copy, Debug, Deserialize)]
 pub struct HitBox {
-    pub center : [f32;2], //x,y : center of hitbox
+    pub center : [f32;3], //x,height,depth : center of hitbox
     pub theta : f32, //rotation of hitbox (around axis outside the screen)
     pub dx : f32, //width of hitbox
     pub dy : f32, //height of hitbox
@@ -53,6 +119,24 @@ pub struct HitBox {
 }
 
 impl HitBox {
+    //boxes with no volume never hit or get hit
+    pub fn is_empty(self : &Self) -> bool {
+        self.dx == 0.0 || self.dy == 0.0 || self.dz == 0.0
+    }
+
+    //boxes of movement nodes are defined relative to a fighter facing east.
+    //returns the box in world coordinates, placed at the fighter's position and mirrored by its facing
+    pub fn placed(self : &Self, position : &FighterPosition, facing_east : bool) -> Self {
+        let sign = if facing_east {1.0} else {-1.0};
+        Self {
+            center : [position.x + sign * self.center[0],
+                      position.z + self.center[1],
+                      position.y + self.center[2]],
+            theta : sign * self.theta,
+            ..*self
+        }
+    }
+
     pub fn bubble_intersection(self : &Self, other : &Self) -> bool {
         let r1 = ((self.dx/2.0).powi(2) + (self.dy/2.0).powi(2)).sqrt();
         let r2 = ((other.dx/2.0).powi(2) + (other.dy/2.0).powi(2)).sqrt();
@@ -67,59 +151,54 @@ impl HitBox {
         }
     }
 
+    //unit vectors of the box's width and height directions
+    fn axes(self : &Self) -> [Vec2;2] {
+        let (s,c) = self.theta.sin_cos();
+        [Vec2::new(c, s), Vec2::new(-s, c)]
+    }
+
+    //half length of the box's shadow on a unit axis
+    fn projected_radius(self : &Self, axis : Vec2) -> f32 {
+        let [u, v] = self.axes();
+        self.dx/2.0 * axis.dot(u).abs() + self.dy/2.0 * axis.dot(v).abs()
+    }
+
+    //center of the overlap of the boxes' bounding rectangles, in world x,y,z coordinates.
+    //for intersecting boxes this is where they touch
+    pub fn contact_point(self : &Self, other : &Self) -> [f32;3] {
+        let overlap_center = |center : f32, radius : f32, other_center : f32, other_radius : f32| {
+            ((center - radius).max(other_center - other_radius) + (center + radius).min(other_center + other_radius)) / 2.0
+        };
+        [overlap_center(self.center[0], self.projected_radius(Vec2::X), other.center[0], other.projected_radius(Vec2::X)),
+         overlap_center(self.center[2], self.dz/2.0, other.center[2], other.dz/2.0),
+         overlap_center(self.center[1], self.projected_radius(Vec2::Y), other.center[1], other.projected_radius(Vec2::Y))]
+    }
+
     pub fn intersection(self : &Self, other : &Self) -> bool {
-        //consider 2D intersection: transform other into self's coordinate system,
-        // then check if any of the corners of other are inside self. We use the SE(2) representation
-        //self_SE(2)_other = self_SE(2)_world * world_SE(2)_other
-        //that is:  T = T1_inv * T2
-        let mut xy_overlap = false;
-        
-        let (s1,c1) = self.theta.sin_cos();
-        let r1_inv = Mat2::from_cols(
-            Vec2::new(c1, s1),
-            Vec2::new(-s1, c1),
-        );
-        let t1 = Vec2::new(self.center[0], self.center[1]);
-        let t1_inv = -r1_inv * t1;
-
-        let (s2,c2) = other.theta.sin_cos();
-        let r2 = Mat2::from_cols(
-            Vec2::new(c2, s2),
-            Vec2::new(-s2, c2),
-        );
-        let t2 = Vec2::new(other.center[0], other.center[1]);
-
-        let r = r1_inv * r2;
-        let t = r1_inv * t2 + t1_inv;
-
-        let p1 = Vec2::new(other.dx/2.0, other.dy/2.0);
-        let p2 = Vec2::new(other.dx/2.0, -other.dy/2.0);
-        let p3 = Vec2::new(-other.dx/2.0, other.dy/2.0);
-        let p4 = Vec2::new(-other.dx/2.0, -other.dy/2.0);
-        for p in [p1,p2,p3,p4].into_iter() {
-            let p = r * p + t;
-            let in_x = p.x > -self.dx/2.0 && p.x < self.dx/2.0;
-            let in_y = p.y > -self.dy/2.0 && p.y < self.dy/2.0;
-            if in_x && in_y {
-                xy_overlap = true
-            }
+        //boxes are only rotated around the depth axis, so they intersect iff they overlap in depth
+        //and their rectangles overlap in the x,y plane.
+        //for the rectangles we use the separating axis theorem: two convex shapes are disjoint iff
+        //their projections on some axis don't overlap, and for rectangles it suffices to check
+        //the 4 edge normals. boxes that only touch are not considered intersecting
+        let depth_overlap = (other.center[2] - self.center[2]).abs() < other.dz/2.0 + self.dz/2.0;
+        if !depth_overlap {
+            return false
         }
 
-        //now check z
-        if xy_overlap {
-            let z_overlap = other.dz/2.0 + self.dz/2.0 > (other.center[1] - self.center[1]).abs();
-            if z_overlap {
-                return true
+        let d = Vec2::new(other.center[0] - self.center[0], other.center[1] - self.center[1]);
+        for axis in self.axes().into_iter().chain(other.axes()) {
+            if d.dot(axis).abs() >= self.projected_radius(axis) + other.projected_radius(axis) {
+                return false
             }
         }
-        false
+        true
     }
 }
 
 impl Default for HitBox {
     fn default() -> Self {
         Self{
-            center : [0.0,0.0],
+            center : [0.0,0.0,0.0],
             theta : 0.0,
             dx : 0.0,
             dy : 0.0,
@@ -131,18 +210,22 @@ impl Default for HitBox {
 #[derive(Component)]
 pub struct FighterHitBox {
     pub hitbox : HitBox,
+    pub lane_tolerance : f32, //y distance to the defender's hurt box beyond which the hit whiffs
     pub damage : f32,
-    pub knockback : f32,
-    pub stun : f32,
+    pub knockback : [f32;2], //away from the attacker, up
+    pub stun : f32, //seconds the defender can't be controlled
+    pub landed : bool, //true once the current swing hit, so it can't hit again
 }
 
 impl Default for FighterHitBox {
     fn default() -> Self {
         Self{
             hitbox : HitBox::default(),
+            lane_tolerance : 0.0,
             damage : 0.0,
-            knockback : 0.0,
+            knockback : [0.0,0.0],
             stun : 0.0,
+            landed : false,
         }
     }
 }
@@ -164,7 +247,7 @@ impl Default for FighterHurtBox {
     }
 }
 
-#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, Display, PartialEq, Eq, Hash, Deserialize)]
 pub enum FighterMovement {
     Idle,
     Slashing,
@@ -182,6 +265,25 @@ pub enum FighterMovement {
     Docking,
     InAir,
     JumpAttack,
+    HitStun,
+    KnockedBack,
+    KnockedDown,
+    BlockStun,
+    GuardBreak,
+    Backdash,
+    Fireball,
+    Grabbing,
+    Throwing,
+    Thrown,
+    ThrowBreak,
+    Staggered,
+    Juggled,
+    WallBounce,
+    GroundBounce,
+    KO,
+    FireballEX,
+    Uppercut,
+    ShoulderCharge,
 }
 
 #[derive(Component)]
@@ -204,11 +306,20 @@ impl FighterMovementStack {
 pub struct FighterBundle{
     pub fighter: Fighter,
     pub health: FighterHealth,
+    pub combo: FighterCombo,
+    pub juggle: FighterJuggle,
+    pub guard: FighterGuard,
+    pub meter: FighterMeter,
+    pub stamina: FighterStamina,
+    pub parry: FighterParry,
     pub hitbox: FighterHitBox,
     pub hurtbox: FighterHurtBox,
+    pub pushbox: FighterPushBox,
     pub position: FighterPosition,
     pub velocity: FighterVelocity,
     pub facing_east: FacingEast,
+    pub stun: FighterStun,
+    pub hitstop: FighterHitstop,
     pub movement_stack : FighterMovementStack,
     pub event_keytargetset_stack : KeyTargetSetStack,
     pub sprite: SpriteSheetBundle,
@@ -276,11 +387,21 @@ impl ShadowBundle {
     }
 }
 
+//the fighter stat a stat bar displays
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FighterStat {
+    Health,
+    Guard,
+    Meter,
+    Stamina,
+}
+
 #[derive(Component)]
 pub struct StatBarData {
     pub max_length: f32,
     pub thickness: f32,
     pub target_entity : Entity,
+    pub stat : FighterStat,
 }
 
 #[derive(Bundle)]
@@ -301,6 +422,7 @@ impl StatBarBundle
         hide: bool,
         z : f32,
         target_entity: Entity,
+        stat : FighterStat,
     ) -> Self
     {
         Self {
@@ -323,6 +445,7 @@ impl StatBarBundle
                 max_length : max_length,
                 thickness : thickness,
                 target_entity : target_entity,
+                stat : stat,
             }
         }
     }
@@ -336,6 +459,7 @@ impl StatBarBundle
         reverse: bool,
         hide: bool,
         target_entity: Entity,
+        stat : FighterStat,
         z : f32,
     ) -> (Self, StatBarEmptyBundle)
     {
@@ -346,7 +470,8 @@ impl StatBarBundle
             reverse,
             hide,
             z,
-            target_entity);
+            target_entity,
+            stat);
 
         let empty = StatBarEmptyBundle::new(empty_color,
             reverse,
@@ -358,6 +483,35 @@ impl StatBarBundle
     }
 }
 
+//a divider between two segments of a stat bar, drawn over it
+#[derive(Bundle)]
+pub struct StatBarDividerBundle
+{
+    sprite_bundle: SpriteBundle,
+}
+
+impl StatBarDividerBundle {
+    //dividers splitting a bar into equal segments, to be spawned as children of the bar
+    pub fn new(color : Color, bar_reverse : bool, bar_max_length : f32, bar_thickness : f32, segments : u32, bar_z : f32) -> Vec<Self> {
+        let dz = 1.0;
+        let sign = if bar_reverse {-1.0} else {1.0};
+        (1..segments).map(|segment| Self {
+            sprite_bundle: SpriteBundle {
+                sprite: Sprite {
+                    color : color,
+                    rect : Some(Rect {min :  Vec2::new(0.0, 0.0),
+                                    max : Vec2::new(2.0, bar_thickness),}),
+                    anchor : bevy::sprite::Anchor::TopCenter,
+                    ..default()
+                },
+                transform: Transform::from_translation(Vec3::new(
+                    sign * bar_max_length * segment as f32 / segments as f32, 0.0, bar_z + dz)),
+                ..default()
+            },
+        }).collect()
+    }
+}
+
 #[derive(Bundle)]
 pub struct StatBarEmptyBundle
 {
@@ -385,7 +539,7 @@ impl StatBarEmptyBundle {
 }
 }
 
-#[derive(Eq, Hash, PartialEq, Clone, Copy, Ord, PartialOrd, Debug)]
+#[derive(Eq, Hash, PartialEq, Clone, Copy, Ord, PartialOrd, Debug, Deserialize)]
 pub enum KeyTarget{
     Up,
     UpJustPressed,
@@ -403,7 +557,7 @@ pub enum KeyTarget{
     DefendJustPressed,
 }
 
-#[derive(Hash, Eq, PartialEq, Clone, Debug)]
+#[derive(Hash, Eq, PartialEq, Clone, Debug, Deserialize)]
 pub struct KeyTargetSet(BTreeSet<KeyTarget>);
 
 impl Display for KeyTargetSet {
@@ -441,6 +595,14 @@ impl KeyTargetSet {
     pub fn contains(&self, other: &KeyTarget) -> bool {
         self.0.contains(other)
     }
+
+    //every non-empty subset of the set
+    pub fn subsets(&self) -> Vec<Self> {
+        let keys : Vec<KeyTarget> = self.0.iter().cloned().collect();
+        (1..1usize << keys.len()).map(|mask| {
+            Self(keys.iter().enumerate().filter(|(i, _)| mask & (1 << i) != 0).map(|(_, key)| *key).collect())
+        }).collect()
+    }
 }
 
 impl<const N: usize> From<[KeyTarget; N]> for KeyTargetSet {
@@ -569,4 +731,87 @@ impl PlayerControls {
     pub fn into_full_keytargetset(&self, keyboard_input : &Input<KeyCode>) -> KeyTargetSet {
         self.into_persistent_keytargetset(keyboard_input) + self.into_event_keytargetset(keyboard_input)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_4;
+
+    fn hitbox(center : [f32;3], theta : f32, dx : f32, dy : f32, dz : f32) -> HitBox {
+        HitBox{center : center, theta : theta, dx : dx, dy : dy, dz : dz}
+    }
+
+    //intersection has to agree with itself both ways
+    fn intersect(a : &HitBox, b : &HitBox) -> bool {
+        let result = a.intersection(b);
+        assert_eq!(result, b.intersection(a), "intersection isn't symmetric for {:?} and {:?}", a, b);
+        result
+    }
+
+    #[test]
+    fn intersection_is_symmetric() {
+        let boxes = [
+            hitbox([0.0, 0.0, 0.0], 0.0, 2.0, 2.0, 2.0),
+            hitbox([1.5, 0.5, 0.0], 0.3, 2.0, 1.0, 2.0),
+            hitbox([0.0, 3.0, 0.5], -0.7, 4.0, 0.5, 1.0),
+            hitbox([2.2, 2.2, 0.0], FRAC_PI_4, 2.0, 2.0, 2.0),
+            hitbox([0.0, 0.0, 5.0], 0.0, 2.0, 2.0, 2.0),
+        ];
+        for a in boxes.iter() {
+            for b in boxes.iter() {
+                intersect(a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn contained_box_intersects() {
+        let outer = hitbox([0.0, 0.0, 0.0], 0.0, 10.0, 10.0, 10.0);
+        let inner = hitbox([1.0, -1.0, 1.0], 0.4, 1.0, 2.0, 1.0);
+        assert!(intersect(&outer, &inner));
+        assert!(intersect(&outer, &outer));
+    }
+
+    #[test]
+    fn touching_boxes_dont_intersect() {
+        let a = hitbox([0.0, 0.0, 0.0], 0.0, 2.0, 2.0, 2.0);
+        //sharing an edge in x, in height, and a face in depth
+        assert!(!intersect(&a, &hitbox([2.0, 0.0, 0.0], 0.0, 2.0, 2.0, 2.0)));
+        assert!(!intersect(&a, &hitbox([0.0, 2.0, 0.0], 0.0, 2.0, 2.0, 2.0)));
+        assert!(!intersect(&a, &hitbox([0.0, 0.0, 2.0], 0.0, 2.0, 2.0, 2.0)));
+        //overlapping just barely
+        assert!(intersect(&a, &hitbox([1.99, 0.0, 0.0], 0.0, 2.0, 2.0, 2.0)));
+        assert!(intersect(&a, &hitbox([0.0, 0.0, 1.99], 0.0, 2.0, 2.0, 2.0)));
+    }
+
+    #[test]
+    fn rotation_is_honored() {
+        //a long thin box reaches the other box only when rotated towards it
+        let target = hitbox([1.3, 1.3, 0.0], 0.0, 1.0, 1.0, 2.0);
+        assert!(!intersect(&hitbox([0.0, 0.0, 0.0], 0.0, 4.0, 0.5, 2.0), &target));
+        assert!(intersect(&hitbox([0.0, 0.0, 0.0], FRAC_PI_4, 4.0, 0.5, 2.0), &target));
+        //a diamond misses a box inside its bounding rectangle
+        let diamond = hitbox([0.0, 0.0, 0.0], FRAC_PI_4, 2.0, 2.0, 2.0);
+        assert!(!intersect(&diamond, &hitbox([2.2, 2.2, 0.0], 0.0, 2.0, 2.0, 2.0)));
+        assert!(intersect(&diamond, &hitbox([1.5, 0.0, 0.0], 0.0, 2.0, 2.0, 2.0)));
+    }
+
+    #[test]
+    fn crossed_boxes_intersect() {
+        //no corner of either box lies inside the other
+        let wide = hitbox([0.0, 0.0, 0.0], 0.0, 10.0, 1.0, 2.0);
+        let tall = hitbox([0.0, 0.0, 0.0], 0.0, 1.0, 10.0, 2.0);
+        assert!(intersect(&wide, &tall));
+        assert!(intersect(&wide, &hitbox([1.0, 0.5, 0.0], 0.3, 1.0, 10.0, 2.0)));
+    }
+
+    #[test]
+    fn placed_box_uses_height_and_depth() {
+        let hitbox = hitbox([10.0, 20.0, 30.0], 0.5, 1.0, 1.0, 1.0);
+        let position = FighterPosition{x : 1.0, y : 2.0, z : 3.0};
+        let placed = hitbox.placed(&position, false);
+        assert_eq!(placed.center, [1.0 - 10.0, 3.0 + 20.0, 2.0 + 30.0]);
+        assert_eq!(placed.theta, -0.5);
+    }
+}
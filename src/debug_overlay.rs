@@ -0,0 +1,84 @@
+use super::components_bundles::*;
+use super::utils::*;
+
+use bevy::prelude::*;
+
+const DEBUG_OVERLAY_KEY : KeyCode = KeyCode::F1;
+const DEBUG_OVERLAY_HEIGHT : f32 = 48.0; //above the fighter's position
+
+#[derive(Component)]
+pub struct DebugOverlayData {
+    pub target_entity : Entity,
+}
+
+//text above a fighter showing its movement and hurt box state, hidden until toggled
+#[derive(Bundle)]
+pub struct DebugOverlayBundle {
+    text_bundle : Text2dBundle,
+    data : DebugOverlayData,
+}
+
+impl DebugOverlayBundle {
+    pub fn new(target_entity : Entity, z : f32) -> Self {
+        Self {
+            text_bundle : Text2dBundle {
+                text : Text::from_section("", TextStyle {
+                    font_size : 14.0,
+                    color : Color::WHITE,
+                    ..default()
+                }).with_alignment(TextAlignment::Center),
+                transform : Transform::from_translation(Vec3::new(0.0, 0.0, z)),
+                visibility : Visibility::Hidden,
+                ..default()
+            },
+            data : DebugOverlayData {
+                target_entity : target_entity,
+            },
+        }
+    }
+}
+
+pub fn update_debug_overlay(keyboard_input: Res<Input<KeyCode>>,
+                        query_fighter: Query<(&FighterMovementStack,
+                                            &FighterPosition,
+                                            &FighterStun,
+                                            &FighterHurtBox,)>,
+                        mut query_overlay: Query<(&DebugOverlayData,
+                                                &mut Text,
+                                                &mut Transform,
+                                                &mut Visibility,)>) {
+    let toggle = keyboard_input.just_pressed(DEBUG_OVERLAY_KEY);
+    for (data,
+        mut text,
+        mut transform,
+        mut visibility) in query_overlay.iter_mut() {
+        if toggle {
+            *visibility = match *visibility {
+                Visibility::Hidden => Visibility::Visible,
+                _ => Visibility::Hidden,
+            };
+        }
+        if *visibility == Visibility::Hidden {continue};
+
+        if let Ok((movement_stack,
+                position,
+                stun,
+                hurtbox)) = query_fighter.get(data.target_entity) {
+            let movement = match movement_stack.last() {
+                Some(durative_movement) => durative_movement.value.to_string(),
+                None => "-".to_string(),
+            };
+            let mut state = format!("{}\nstun {:.2}", movement, stun.0);
+            if hurtbox.invunerable {
+                state.push_str("\ninvunerable");
+            }
+            if hurtbox.armor {
+                state.push_str("\narmor");
+            }
+            text.sections[0].value = state;
+
+            let uvw = project_xyz_2_uvw([position.x, position.y, position.z + DEBUG_OVERLAY_HEIGHT]);
+            transform.translation = Vec3::new(uvw[0], uvw[1], transform.translation.z);
+        }
+    }
+}
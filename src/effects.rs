@@ -0,0 +1,69 @@
+use super::combat::*;
+use super::utils::*;
+use super::{FighterAnimationHash, AnimationTimer};
+
+use bevy::prelude::*;
+
+//effect sprites are the folders under textures/effects
+const HIT_EFFECT : &str = "HitSpark";
+const BLOCK_EFFECT : &str = "BlockSpark";
+const PARRY_EFFECT : &str = "ParrySpark";
+pub(crate) const EFFECT_NAMES : [&str;3] = [HIT_EFFECT, BLOCK_EFFECT, PARRY_EFFECT];
+const EFFECT_Z_OFFSET : f32 = 1.0; //drawn over the fighters at the contact point
+
+#[derive(Resource)]
+pub(crate) struct EffectsAnimation(pub(crate) FighterAnimationHash);
+
+//a short animation that plays once and despawns
+#[derive(Component)]
+pub struct Effect {
+    pub sprite_name : String,
+}
+
+fn effect_sprite_name(kind : HitKind) -> &'static str {
+    match kind {
+        HitKind::Blocked => BLOCK_EFFECT,
+        HitKind::Parried => PARRY_EFFECT,
+        _ => HIT_EFFECT,
+    }
+}
+
+pub(crate) fn spawn_effects(mut commands: Commands,
+                            mut hit_events: EventReader<HitEvent>,
+                            effects_animation: Option<Res<EffectsAnimation>>,) {
+    let Some(effects_animation) = effects_animation else {return};
+    for event in hit_events.iter() {
+        let sprite_name = effect_sprite_name(event.kind);
+        //effects without sprites are skipped
+        let Some(effect_indicies) = effects_animation.0.hashmap.get(sprite_name) else {continue};
+        let uvw = project_xyz_2_uvw(event.contact_point);
+        commands.spawn((Effect {
+            sprite_name : sprite_name.to_string(),
+        },
+        SpriteSheetBundle {
+            texture_atlas : effects_animation.0.atlas_handle.clone(),
+            sprite : TextureAtlasSprite {
+                index : effect_indicies[0],
+                ..default()
+            },
+            transform : Transform::from_translation(Vec3::new(uvw[0], uvw[1], uvw[2] + EFFECT_Z_OFFSET)),
+            ..default()
+        },));
+    }
+}
+
+pub(crate) fn update_effects(mut commands: Commands,
+                            animation_timer: Res<AnimationTimer>,
+                            effects_animation: Option<Res<EffectsAnimation>>,
+                            mut query: Query<(Entity, &Effect, &mut TextureAtlasSprite)>) {
+    let Some(effects_animation) = effects_animation else {return};
+    if !animation_timer.just_finished() {return};
+    for (entity, effect, mut sprite) in query.iter_mut() {
+        let effect_indicies = effects_animation.0.hashmap.get(&effect.sprite_name).unwrap();
+        if sprite.index >= effect_indicies[1] {
+            commands.entity(entity).despawn();
+        } else {
+            sprite.index += 1;
+        }
+    }
+}
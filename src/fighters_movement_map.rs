@@ -5,61 +5,211 @@ use std::collections::HashMap;
 use std::sync::Arc;
 
 use bevy::prelude::*;
+use serde::Deserialize;
 
 //movement
-pub const WALKING_SPEED : f32 = 100.0;
-pub const RUNNING_SPEED : f32 = 200.0;
-pub const JUMPING_SPEED : f32 = 200.0;
-pub const GRAVITY : f32 = -400.0;
+pub const FRICTION : f32 = 4.0; //fraction of sliding velocity lost per second
+
+//meter
+pub const METER_SEGMENT : f32 = 100.0;
+pub const METER_SEGMENTS : u32 = 3;
+
+//bounces off the stage bounds. only the heaviest knockback bounces, e.g. a shoulder charge into a wall
+//or an uppercut into the floor, normal hits, fireballs and throws are slower
+pub const WALL_BOUNCE_SPEED : f32 = 200.0; //slower fighters stop at the wall
+pub const WALL_BOUNCE_RESTITUTION : f32 = 0.5; //fraction of the speed kept after a bounce
+pub const GROUND_BOUNCE_SPEED : f32 = 280.0; //slower fighters land
+pub const GROUND_BOUNCE_RESTITUTION : f32 = 0.4;
+
+//juggles
+pub const JUGGLE_GRAVITY_SCALING : f32 = 0.15; //fraction of the fighter's gravity added for every juggle hit taken
+pub const JUGGLE_POINTS : u32 = 4; //juggle hits a fighter can take before it can't be hit until landing
+
+//throws
+pub const GRAB_KEYS : [KeyTarget;2] = [KeyTarget::AttackJustPressed, KeyTarget::DefendJustPressed];
+//hits only connect between fighters this close across the y-lane
+pub const DEFAULT_LANE_TOLERANCE : f32 = 12.0;
+
+//phases of an attack, derived from the frames of its hit boxes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MovementPhase {
+    Startup, //before the first active frame
+    Active, //a hit box is out
+    Recovery, //after the first active frame, with no hit box out
+}
+
+//a box that exists during the animation frames [frames[0], frames[1]] of its movement node.
+//frames are counted from the first sprite of the node's animation
+//boxes without frames exist for the whole animation. hit boxes carry what their hit does,
+//hurt boxes leave it out
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct FramedHitBox {
+    pub hitbox : HitBox,
+    #[serde(default = "FramedHitBox::all_frames")]
+    pub frames : [usize;2],
+    #[serde(default)]
+    pub damage : f32,
+    #[serde(default)]
+    pub knockback : [f32;2], //away from the attacker, up
+    #[serde(default)]
+    pub stun : f32, //seconds the defender can't be controlled
+}
+
+impl FramedHitBox {
+    fn all_frames() -> [usize;2] {
+        [0, usize::MAX]
+    }
+
+    pub fn contains(&self, frame : usize) -> bool {
+        self.frames[0] <= frame && frame <= self.frames[1]
+    }
+}
+
+//what sets the fighters apart, read by the movement behaviors
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct FighterStats {
+    pub walking_speed : f32,
+    pub running_speed : f32,
+    pub jumping_speed : f32, //vertical velocity a jump starts with
+    pub gravity : f32,
+    pub weight : f32, //knockback the fighter takes is divided by its weight
+    pub health : f32,
+    pub parry_window : f32, //seconds before a hit in which pressing defend while docking parries it
+    pub stamina_drain_rate : f32, //per second of running and dashing
+    pub stamina_recovery_rate : f32, //per second of walking or standing
+}
+
+//the behaviors a movement node is made of. they are closures, so one behavior can be built
+//with different parameters, e.g. walking in any direction
+pub type StateUpdateFn = Arc<dyn Fn(&mut FighterPosition, &mut FighterVelocity, f32, &FighterStats) + Send + Sync>;
+pub type StateEnterFn = Arc<dyn Fn(&mut FighterPosition, &mut FighterVelocity, &mut FacingEast, &FighterStats) + Send + Sync>;
+//floor_z, position_z, movement_stack, keytargetset_stack, queried_by_joined_keytargetset
+pub type EventEnterFn = Arc<dyn Fn(f32, f32, &FighterMovementStack, &mut KeyTargetSetStack, bool) -> bool + Send + Sync>;
+//floor_z, position_z
+pub type EnterFn = Arc<dyn Fn(f32, f32) -> bool + Send + Sync>;
+//floor_z, position_z, movement_duration, movement_request
+pub type ExitFn = Arc<dyn Fn(f32, f32, f32, &FighterMovement) -> bool + Send + Sync>;
+pub type ChannelFn = Arc<dyn Fn(&KeyTargetSet, &mut FighterVelocity, &FighterStats) + Send + Sync>;
+
+//wrap closures into behaviors, so their argument types are inferred
+pub fn state_update_fn(f : impl Fn(&mut FighterPosition, &mut FighterVelocity, f32, &FighterStats) + Send + Sync + 'static) -> StateUpdateFn {
+    Arc::new(f)
+}
 
+pub fn state_enter_fn(f : impl Fn(&mut FighterPosition, &mut FighterVelocity, &mut FacingEast, &FighterStats) + Send + Sync + 'static) -> StateEnterFn {
+    Arc::new(f)
+}
+
+pub fn event_enter_fn(f : impl Fn(f32, f32, &FighterMovementStack, &mut KeyTargetSetStack, bool) -> bool + Send + Sync + 'static) -> EventEnterFn {
+    Arc::new(f)
+}
+
+pub fn enter_fn(f : impl Fn(f32, f32) -> bool + Send + Sync + 'static) -> EnterFn {
+    Arc::new(f)
+}
+
+pub fn exit_fn(f : impl Fn(f32, f32, f32, &FighterMovement) -> bool + Send + Sync + 'static) -> ExitFn {
+    Arc::new(f)
+}
+
+pub fn channel_fn(f : impl Fn(&KeyTargetSet, &mut FighterVelocity, &FighterStats) + Send + Sync + 'static) -> ChannelFn {
+    Arc::new(f)
+}
+
+#[derive(Clone, Deserialize)]
 pub struct DurationAndFallback {
     pub duration : f32,
     pub fallback : FighterMovement,
+    #[serde(default)]
     pub apply_enter_state_fcn : bool
 }
 
 pub struct FighterMovementNodeBase {
     pub movement: FighterMovement,
     pub sprite_name : String,
-    pub state_update : fn(fighter_position : &mut FighterPosition,
-                    fighter_velocity : &mut FighterVelocity,
-                    delta_time : f32),
-    pub state_enter : fn(fighter_position : &mut FighterPosition,
-                   fighter_velocity : &mut FighterVelocity,
-                   facing_east : &mut FacingEast),
+    pub state_update : StateUpdateFn,
+    pub state_enter : StateEnterFn,
+}
+
+//a projectile a movement node launches at one of its animation frames.
+//offset, velocity and hitbox are for a fighter facing east
+#[derive(Clone, Deserialize)]
+pub struct ProjectileSpawn {
+    pub frame : usize,
+    pub sprite_name : String,
+    pub offset : [f32;3], //from the fighter's position
+    pub velocity : [f32;3],
+    pub hitbox : HitBox, //relative to the projectile's position
+    pub lane_tolerance : f32,
+    pub damage : f32,
+    pub knockback : [f32;2],
+    pub stun : f32,
+    pub lifetime : f32, //seconds until the projectile vanishes
+}
+
+//a grab that only connects at close range, and goes through docking.
+//once it connects the thrower and its opponent are forced into paired movements
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct Throw {
+    pub frames : [usize;2], //animation frames the grab is active
+    pub range : [f32;2], //reach in front of the fighter, and across the y-lane
+    pub hold_distance : f32, //the opponent is held this far in front of the thrower
+    pub hold_movement : FighterMovement, //forced onto the thrower
+    pub opponent_movement : FighterMovement, //forced onto the opponent
+    pub duration : f32, //seconds both fighters are locked in their paired movements
+    pub tech_window : f32, //seconds the opponent has to press grab and break free
+    pub damage : f32,
+    pub knockback : [f32;2],
+    pub stun : f32,
 }
 
 pub struct EventFighterMovementNode {
     pub base : FighterMovementNodeBase,
-    pub player_can_enter : fn(floor_z : f32,
-        position_z : f32,
-        movement_stack : &FighterMovementStack,
-        keytargetset_stack : &mut KeyTargetSetStack,
-        queried_by_joined_keytargetset : bool) -> bool,
-    pub player_can_exit : fn(floor_z : f32,
-            position_z : f32,
-            movement_duration : f32,
-            movement_request : &FighterMovement) -> bool,
-    pub channel : Option<fn (full_keyset : &KeyTargetSet, fighter_velocity : &mut FighterVelocity)>,
+    pub player_can_enter : EventEnterFn,
+    pub player_can_exit : ExitFn,
+    pub min_duration : f32, //seconds before the player can exit the node
+    pub channel : Option<ChannelFn>,
     pub duration_and_fallback : Option<DurationAndFallback>,
-    pub hit_boxes : Vec<HitBox>,
-    pub hurt_boxes : Vec<HitBox>,
+    pub hit_boxes : Vec<FramedHitBox>,
+    pub active_frames : Option<[usize;2]>, //active without a hit box out, e.g. while launching a projectile
+    pub lane_tolerance : f32, //y distance between the fighters beyond which the hit boxes whiff
+    pub hurt_boxes : Vec<FramedHitBox>,
+    pub invunerable_frames : Option<[usize;2]>, //hits pass through the fighter
+    pub armor_frames : Option<[usize;2]>, //hits deal damage without a hit reaction
+    pub projectile : Option<ProjectileSpawn>,
+    pub throw : Option<Throw>,
+    pub meter_cost : f32, //spent when the player enters the node
+}
+
+impl EventFighterMovementNode {
+    //None for nodes without hit boxes or active frames, they have no attack phases
+    pub fn phase(&self, frame : usize) -> Option<MovementPhase> {
+        let first_active_frame = self.hit_boxes.iter().map(|x| x.frames[0]).chain(self.active_frames.map(|x| x[0])).min()?;
+        if self.hit_boxes.iter().any(|x| x.contains(frame)) || frames_contain(&self.active_frames, frame) {
+            Some(MovementPhase::Active)
+        } else if frame < first_active_frame {
+            Some(MovementPhase::Startup)
+        } else {
+            Some(MovementPhase::Recovery)
+        }
+    }
 }
 
 pub struct PersistentFighterMovementNode {
     pub base : FighterMovementNodeBase,
-    pub player_can_enter : fn(floor_z : f32, position_z : f32,) -> bool,
-    pub player_can_exit : fn(floor_z : f32,
-            position_z : f32,
-            movement_duration : f32,
-            movement_request : &FighterMovement) -> bool,
+    pub player_can_enter : EnterFn,
+    pub player_can_exit : ExitFn,
     pub hit_box : HitBox,
     pub hurt_box : HitBox,
 }
 
 pub struct UncontrollableFighterMovementNode {
     pub base : FighterMovementNodeBase,
-    pub player_can_enter : fn(floor_z : f32, position_z : f32,) -> bool,
+    pub player_can_enter : EnterFn,
+    pub landing_fallback : Option<FighterMovement>, //entered when the fighter reaches the floor
+    pub bounces : bool, //knocked into a wall or the floor fast enough, the fighter bounces off it
+    pub invunerable : bool,
+    pub armor : bool,
     pub hit_box : HitBox,
     pub hurt_box : HitBox,
 }
@@ -68,10 +218,12 @@ pub trait FighterMovementNodeTrait {
     fn movement(&self) -> FighterMovement;
     fn state_enter(&self, fighter_position : &mut FighterPosition,
                         fighter_velocity : &mut FighterVelocity,
-                        facing_east : &mut FacingEast) -> ();
+                        facing_east : &mut FacingEast,
+                        stats : &FighterStats) -> ();
     fn state_update(&self, fighter_position : &mut FighterPosition,
                         fighter_velocity : &mut FighterVelocity,
-                        delta_time : f32) -> ();
+                        delta_time : f32,
+                        stats : &FighterStats) -> ();
     fn sprite_name(&self) -> &String;
 }
 
@@ -83,11 +235,12 @@ macro_rules! impl_fighter_movement_node_trait {
             }
             fn state_enter(&self, fighter_position: &mut FighterPosition,
                                  fighter_velocity: &mut FighterVelocity,
-                                 facing_east: &mut FacingEast) {
-                (self.base.state_enter)(fighter_position, fighter_velocity, facing_east);
+                                 facing_east: &mut FacingEast,
+                                 stats: &FighterStats) {
+                (self.base.state_enter)(fighter_position, fighter_velocity, facing_east, stats);
             }
-            fn state_update(&self, fighter_position: &mut FighterPosition, fighter_velocity: &mut FighterVelocity, delta_time: f32) {
-                (self.base.state_update)(fighter_position, fighter_velocity, delta_time);
+            fn state_update(&self, fighter_position: &mut FighterPosition, fighter_velocity: &mut FighterVelocity, delta_time: f32, stats: &FighterStats) {
+                (self.base.state_update)(fighter_position, fighter_velocity, delta_time, stats);
             }
             fn sprite_name(&self) -> &String {
                 &self.base.sprite_name
@@ -106,6 +259,68 @@ pub enum FighterMovementNode {
     Uncontrollable(Arc<UncontrollableFighterMovementNode>),
 }
 
+fn frames_contain(frames : &Option<[usize;2]>, frame : usize) -> bool {
+    match frames {
+        Some(frames) => frames[0] <= frame && frame <= frames[1],
+        None => false,
+    }
+}
+
+impl FighterMovementNode {
+    pub fn invunerable(&self, frame : usize) -> bool {
+        match self {
+            FighterMovementNode::EventTriggered(node) => {frames_contain(&node.invunerable_frames, frame)},
+            FighterMovementNode::Persistent(_) => {false},
+            FighterMovementNode::Uncontrollable(node) => {node.invunerable},
+        }
+    }
+
+    pub fn armor(&self, frame : usize) -> bool {
+        match self {
+            FighterMovementNode::EventTriggered(node) => {frames_contain(&node.armor_frames, frame)},
+            FighterMovementNode::Persistent(_) => {false},
+            FighterMovementNode::Uncontrollable(node) => {node.armor},
+        }
+    }
+
+    //the hit box of an event triggered node out at the frame, with what its hit does
+    pub fn framed_hit_box(&self, frame : usize) -> Option<&FramedHitBox> {
+        match self {
+            FighterMovementNode::EventTriggered(node) => node.hit_boxes.iter().find(|x| x.contains(frame)),
+            _ => None,
+        }
+    }
+
+    //event triggered nodes hold boxes per animation frame, the first one containing the frame is used.
+    //other nodes have a single box for all frames
+    pub fn hit_box(&self, frame : usize) -> HitBox {
+        match self {
+            FighterMovementNode::EventTriggered(_) => {
+                self.framed_hit_box(frame).map(|x| x.hitbox).unwrap_or_default()
+            },
+            FighterMovementNode::Persistent(node) => {node.hit_box},
+            FighterMovementNode::Uncontrollable(node) => {node.hit_box},
+        }
+    }
+
+    pub fn lane_tolerance(&self) -> f32 {
+        match self {
+            FighterMovementNode::EventTriggered(node) => {node.lane_tolerance},
+            _ => DEFAULT_LANE_TOLERANCE,
+        }
+    }
+
+    pub fn hurt_box(&self, frame : usize) -> HitBox {
+        match self {
+            FighterMovementNode::EventTriggered(node) => {
+                node.hurt_boxes.iter().find(|x| x.contains(frame)).map(|x| x.hitbox).unwrap_or_default()
+            },
+            FighterMovementNode::Persistent(node) => {node.hurt_box},
+            FighterMovementNode::Uncontrollable(node) => {node.hurt_box},
+        }
+    }
+}
+
 impl FighterMovementNodeTrait for FighterMovementNode {
     fn movement(&self) -> FighterMovement {
         match self {
@@ -123,19 +338,19 @@ impl FighterMovementNodeTrait for FighterMovementNode {
         }
     }
 
-    fn state_update(&self, pos : &mut FighterPosition, vel : &mut FighterVelocity, dt : f32) {
+    fn state_update(&self, pos : &mut FighterPosition, vel : &mut FighterVelocity, dt : f32, stats : &FighterStats) {
         match self {
-            FighterMovementNode::EventTriggered(node) => {node.state_update(pos,vel,dt)},
-            FighterMovementNode::Persistent(node) => {node.state_update(pos,vel,dt)},
-            FighterMovementNode::Uncontrollable(node) => {node.state_update(pos,vel,dt)},
+            FighterMovementNode::EventTriggered(node) => {node.state_update(pos,vel,dt,stats)},
+            FighterMovementNode::Persistent(node) => {node.state_update(pos,vel,dt,stats)},
+            FighterMovementNode::Uncontrollable(node) => {node.state_update(pos,vel,dt,stats)},
         };
     }
 
-    fn state_enter(&self, pos : &mut FighterPosition, vel : &mut FighterVelocity, facing_east : &mut FacingEast) {
+    fn state_enter(&self, pos : &mut FighterPosition, vel : &mut FighterVelocity, facing_east : &mut FacingEast, stats : &FighterStats) {
         match self {
-            FighterMovementNode::EventTriggered(node) => {node.state_enter(pos,vel, facing_east)},
-            FighterMovementNode::Persistent(node) => {node.state_enter(pos,vel, facing_east)},
-            FighterMovementNode::Uncontrollable(node) => {node.state_enter(pos,vel, facing_east)}
+            FighterMovementNode::EventTriggered(node) => {node.state_enter(pos,vel, facing_east, stats)},
+            FighterMovementNode::Persistent(node) => {node.state_enter(pos,vel, facing_east, stats)},
+            FighterMovementNode::Uncontrollable(node) => {node.state_enter(pos,vel, facing_east, stats)}
         };
     }
 }
@@ -145,19 +360,29 @@ pub struct FighterMovementMap {
     pub persistent_map : HashMap<KeyTargetSet,Vec<Arc<PersistentFighterMovementNode>>>,
     pub uncontrollable_map : HashMap<FighterMovement,Arc<UncontrollableFighterMovementNode>>,
     pub movement_map : HashMap<FighterMovement, FighterMovementNode>,
+    pub stats : FighterStats,
 }
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum FighterMovementError {
     MovementNotFound(FighterMovement),
+    DuplicateMovement(FighterMovement), //two nodes stand for the same movement
+    BadMoveDefinitions{filepath : String, reason : String}, //a move file can't be read or deserialized
+    UnknownBehavior{slot : &'static str, name : String}, //a move definition names a behavior that isn't registered
+    BadBehaviorParameter{name : String, parameter : String, reason : String}, //a behavior's parameter is missing or malformed
+    OverlappingEntries{keys : KeyTargetSet, airborne : bool, movements : Vec<FighterMovement>}, //more than one node accepts the same input
+    UnreachableMovement(FighterMovement), //a controllable node no other node leads to
+    MissingFallback{movement : FighterMovement, fallback : FighterMovement}, //a node falls back to a movement that isn't in the map
+    MissingSpriteFolder{movement : FighterMovement, sprite_name : String}, //assets.yaml has no folder for the sprite
 }
 
 impl FighterMovementMap {
-    fn new() -> Self {
+    pub(crate) fn new(stats : FighterStats) -> Self {
         Self{
             event_map : HashMap::new(),
             persistent_map : HashMap::new(),
             uncontrollable_map : HashMap::new(),
             movement_map : HashMap::new(),
+            stats : stats,
         }
     }
 
@@ -187,24 +412,53 @@ impl FighterMovementMap {
         }
     }
 
-    pub fn ensure_must_exists_movements(self) -> Self{
-        let must_exist_movements = [FighterMovement::Idle];
-        for movement in must_exist_movements.iter() {
-            if !self.movement_map.contains_key(movement) {
-                panic!("Movement {} must exist in the FighterMovementMap", movement);
+    //movements the game forces fighters into, whatever their move definitions
+    pub fn ensure_must_exists_movements(&self) -> Result<(), Vec<FighterMovementError>> {
+        let must_exist_movements = [
+            FighterMovement::Idle,
+            FighterMovement::InAir,
+            FighterMovement::HitStun,
+            FighterMovement::KnockedBack,
+            FighterMovement::Juggled,
+            FighterMovement::WallBounce,
+            FighterMovement::GroundBounce,
+            FighterMovement::BlockStun,
+            FighterMovement::GuardBreak,
+            FighterMovement::Staggered,
+            FighterMovement::ThrowBreak,
+            FighterMovement::KO,
+        ];
+        let missing_movements = must_exist_movements.iter()
+            .filter(|movement| !self.movement_map.contains_key(movement))
+            .map(|movement| FighterMovementError::MovementNotFound(*movement))
+            .collect::<Vec<_>>();
+        if missing_movements.is_empty() {Ok(())} else {Err(missing_movements)}
+    }
+
+    //sprites of all nodes and the projectiles they launch
+    pub fn sprite_names(&self) -> Vec<&String> {
+        let mut sprite_names : Vec<&String> = self.movement_map.values().map(|x| x.sprite_name()).collect();
+        for nodes in self.event_map.values() {
+            for node in nodes.iter() {
+                if let Some(projectile) = &node.projectile {
+                    sprite_names.push(&projectile.sprite_name);
+                }
             }
         }
-    self
+        sprite_names.sort();
+        sprite_names.dedup();
+        sprite_names
     }
 
-    fn check_if_can_insert_node(&mut self, movement : &FighterMovement) {
-        if self.movement_map.contains_key(&movement) {
-            panic!("Node with fighter movement {} already contained in the movement_map", movement);
+    fn check_if_can_insert_node(&mut self, movement : &FighterMovement) -> Result<(), FighterMovementError> {
+        if self.movement_map.contains_key(movement) {
+            return Err(FighterMovementError::DuplicateMovement(*movement))
         }
+        Ok(())
     }
 
-    fn insert_to_event_map(&mut self, keyset : KeyTargetSet, node : EventFighterMovementNode) {
-        self.check_if_can_insert_node(&node.base.movement);
+    pub(crate) fn insert_to_event_map(&mut self, keyset : KeyTargetSet, node : EventFighterMovementNode) -> Result<(), FighterMovementError> {
+        self.check_if_can_insert_node(&node.base.movement)?;
         let node_movement = node.base.movement.clone();
         let arc_movement_node = Arc::new(node);
         self.movement_map.insert(node_movement, FighterMovementNode::EventTriggered(arc_movement_node.clone()));
@@ -213,10 +467,11 @@ impl FighterMovementMap {
         } else {
             self.event_map.insert(keyset, vec![arc_movement_node]);
         }
+        Ok(())
     }
 
-    fn insert_to_persistent_map(&mut self, keyset : KeyTargetSet, node : PersistentFighterMovementNode) {
-        self.check_if_can_insert_node(&node.base.movement);
+    pub(crate) fn insert_to_persistent_map(&mut self, keyset : KeyTargetSet, node : PersistentFighterMovementNode) -> Result<(), FighterMovementError> {
+        self.check_if_can_insert_node(&node.base.movement)?;
         let node_movement = node.base.movement.clone();
         let arc_movement_node = Arc::new(node);
         self.movement_map.insert(node_movement, FighterMovementNode::Persistent(arc_movement_node.clone()));
@@ -225,482 +480,18 @@ impl FighterMovementMap {
         } else {
             self.persistent_map.insert(keyset, vec![arc_movement_node]);
         }
+        Ok(())
     }
 
-    fn insert_to_uncontrollable_map(&mut self, node : UncontrollableFighterMovementNode) {
-        self.check_if_can_insert_node(&node.base.movement);
+    pub(crate) fn insert_to_uncontrollable_map(&mut self, node : UncontrollableFighterMovementNode) -> Result<(), FighterMovementError> {
+        self.check_if_can_insert_node(&node.base.movement)?;
         let node_movement = node.base.movement.clone();
         let arc_movement_node = Arc::new(node);
         self.movement_map.insert(node_movement, FighterMovementNode::Uncontrollable(arc_movement_node.clone()));
         self.uncontrollable_map.insert(node_movement.clone(), arc_movement_node);
-    }
-}
-
-impl Default for FighterMovementMap {
-    fn default() -> Self {
-        let mut map = Self::new();
-        map.insert_to_uncontrollable_map(UncontrollableFighterMovementNode {
-            base: FighterMovementNodeBase { 
-                movement: FighterMovement::Idle,
-                sprite_name: "Idle".to_string(),
-                state_update: |_,_,_| {},
-                state_enter: |_,vel,_| {vel.x = 0.0; vel.y = 0.0}, 
-            },
-            player_can_enter: |floor_z,z| floor_z == z,
-            hit_box: HitBox::default(),
-            hurt_box: HitBox::default()
-        });
-
-         map.insert_to_uncontrollable_map(UncontrollableFighterMovementNode {
-             base: FighterMovementNodeBase { 
-                 movement: FighterMovement::InAir,
-                 sprite_name: "JumpLoop".to_string(),
-                 state_update: |pos,vel,dt| {
-                    pos.x += vel.x*dt;
-                    pos.y += vel.y*dt;
-                    pos.z += vel.z*dt;
-                    vel.z += GRAVITY*dt;
-                 },
-                 state_enter: |_,_,_| {}, 
-             },
-             player_can_enter: |floor_z,z| floor_z != z,
-             hit_box: HitBox::default(),
-             hurt_box: HitBox::default()
-        });
-                    
-        map.insert_to_persistent_map(KeyTargetSet::from([KeyTarget::Right]),
-        PersistentFighterMovementNode { 
-            base: FighterMovementNodeBase { 
-                movement: FighterMovement::WalkingEast,
-                sprite_name: "Walking".to_string(),
-                state_update: |pos,vel,dt| {
-                    pos.x += vel.x*dt;
-                },
-                state_enter: |_,vel,facing_east| {
-                    vel.x = WALKING_SPEED;
-                    vel.y = 0.0;
-                    facing_east.0 = true;
-                    }, 
-            },
-            player_can_enter: |floor_z, position_z| floor_z == position_z,
-            player_can_exit: |_,_,_,_| true,
-            hit_box: HitBox::default(),
-            hurt_box: HitBox::default(), 
-        });
-
-        map.insert_to_persistent_map(KeyTargetSet::from([KeyTarget::Left]),
-        PersistentFighterMovementNode { 
-            base: FighterMovementNodeBase { 
-                movement: FighterMovement::WalkingWest,
-                sprite_name: "Walking".to_string(),
-                state_update: |pos,vel,dt| {
-                    pos.x += vel.x*dt;
-                },
-                state_enter: |_,vel, facing_east| {
-                    vel.x = -WALKING_SPEED;
-                    vel.y = 0.0;
-                    facing_east.0 = false;
-                    }, 
-            },
-            player_can_enter: |floor_z, position_z| floor_z == position_z,
-            player_can_exit: |_,_,_,_| true,
-            hit_box: HitBox::default(),
-            hurt_box: HitBox::default(), 
-        });
-
-        map.insert_to_persistent_map(KeyTargetSet::from([KeyTarget::Up]),
-        PersistentFighterMovementNode { 
-            base: FighterMovementNodeBase { 
-                movement: FighterMovement::WalkingNorth,
-                sprite_name: "Walking".to_string(),
-                state_update: |pos,vel,dt| {
-                    pos.y += vel.y*dt;
-                },
-                state_enter: |_,vel,_| {
-                    vel.x = 0.0;
-                    vel.y = WALKING_SPEED;
-                    }, 
-            },
-            player_can_enter: |floor_z, position_z| floor_z == position_z,
-            player_can_exit: |_,_,_,_| true,
-            hit_box: HitBox::default(),
-            hurt_box: HitBox::default(), 
-        });
-
-        map.insert_to_persistent_map(KeyTargetSet::from([KeyTarget::Down]),
-        PersistentFighterMovementNode { 
-            base: FighterMovementNodeBase { 
-                movement: FighterMovement::WalkingSouth,
-                sprite_name: "Walking".to_string(),
-                state_update: |pos,vel,dt| {
-                    pos.y += vel.y*dt;
-                },
-                state_enter: |_,vel,_| {
-                    vel.x = 0.0;
-                    vel.y = -WALKING_SPEED;
-                    }, 
-            },
-            player_can_enter: |floor_z, position_z| floor_z == position_z,
-            player_can_exit: |_,_,_,_| true,
-            hit_box: HitBox::default(),
-            hurt_box: HitBox::default(), 
-        });
-
-        map.insert_to_persistent_map(KeyTargetSet::from([KeyTarget::Up,KeyTarget::Right]),
-        PersistentFighterMovementNode { 
-            base: FighterMovementNodeBase { 
-                movement: FighterMovement::WalkingNorthEast,
-                sprite_name: "Walking".to_string(),
-                state_update: |pos,vel,dt| {
-                    pos.x += vel.x*dt;
-                    pos.y += vel.y*dt;
-                },
-                state_enter: |_,vel,facing_east| {
-                    vel.x = WALKING_SPEED/1.41;
-                    vel.y = WALKING_SPEED/1.41;
-                    facing_east.0 = true;
-                    }, 
-            },
-            player_can_enter: |floor_z, position_z| floor_z == position_z,
-            player_can_exit: |_,_,_,_| true,
-            hit_box: HitBox::default(),
-            hurt_box: HitBox::default(), 
-        });
-
-        map.insert_to_persistent_map(KeyTargetSet::from([KeyTarget::Up,KeyTarget::Left]),
-        PersistentFighterMovementNode { 
-            base: FighterMovementNodeBase { 
-                movement: FighterMovement::WalkingNorthWest,
-                sprite_name: "Walking".to_string(),
-                state_update: |pos,vel,dt| {
-                    pos.x += vel.x*dt;
-                    pos.y += vel.y*dt;
-                },
-                state_enter: |_,vel,facing_east| {
-                    vel.x = -WALKING_SPEED/1.41;
-                    vel.y = WALKING_SPEED/1.41;
-                    facing_east.0 = false;
-                    }, 
-            },
-            player_can_enter: |floor_z, position_z| floor_z == position_z,
-            player_can_exit: |_,_,_,_| true,
-            hit_box: HitBox::default(),
-            hurt_box: HitBox::default(), 
-        });
-
-        map.insert_to_persistent_map(KeyTargetSet::from([KeyTarget::Down,KeyTarget::Right]),
-        PersistentFighterMovementNode { 
-            base: FighterMovementNodeBase { 
-                movement: FighterMovement::WalkingSouthEast,
-                sprite_name: "Walking".to_string(),
-                state_update: |pos,vel,dt| {
-                    pos.x += vel.x*dt;
-                    pos.y += vel.y*dt;
-                },
-                state_enter: |_,vel,facing_east| {
-                    vel.x = WALKING_SPEED/1.41;
-                    vel.y = -WALKING_SPEED/1.41;
-                    facing_east.0 = true;
-                    }, 
-            },
-            player_can_enter: |floor_z, position_z| floor_z == position_z,
-            player_can_exit: |_,_,_,_| true,
-            hit_box: HitBox::default(),
-            hurt_box: HitBox::default(), 
-        });
-
-        map.insert_to_persistent_map(KeyTargetSet::from([KeyTarget::Down,KeyTarget::Left]),
-        PersistentFighterMovementNode { 
-            base: FighterMovementNodeBase { 
-                movement: FighterMovement::WalkingSouthWest,
-                sprite_name: "Walking".to_string(),
-                state_update: |pos,vel,dt| {
-                    pos.x += vel.x*dt;
-                    pos.y += vel.y*dt;
-                },
-                state_enter: |_,vel, facing_east| {
-                    vel.x = -WALKING_SPEED/1.41;
-                    vel.y = -WALKING_SPEED/1.41;
-                    facing_east.0 = false;
-                    }, 
-            },
-            player_can_enter: |floor_z, position_z| floor_z == position_z,
-            player_can_exit: |_,_,_,_| true,
-            hit_box: HitBox::default(),
-            hurt_box: HitBox::default(), 
-        });
-
-        map.insert_to_event_map(KeyTargetSet::from([KeyTarget::JumpJustPressed]),
-         EventFighterMovementNode { 
-            base: FighterMovementNodeBase {
-                movement: FighterMovement::Jumping,
-                sprite_name: "JumpLoop".to_string(),
-                state_update: |pos,vel,dt| {
-                    pos.x += vel.x * dt;
-                    pos.y += vel.y * dt;
-                    pos.z += vel.z * dt;
-                    vel.z += GRAVITY * dt;
-                },
-                state_enter: |_,vel,_| {vel.z = JUMPING_SPEED;},
-            }, 
-            player_can_enter: |floor_z,pos_z,_,_,_| floor_z == pos_z,
-            player_can_exit: |floor_z,pos_z,_,movement_request| 
-                {
-                    if movement_request == &FighterMovement::JumpAttack {
-                        return true
-                    } else if pos_z == floor_z {
-                        return true}
-                    else {
-                        return false};
-                },
-            channel: None,
-            duration_and_fallback: None,
-            hit_boxes: Vec::new(),
-            hurt_boxes: Vec::new(),
-         });
-
-         map.insert_to_event_map(KeyTargetSet::from([KeyTarget::RightJustPressed]),
-         EventFighterMovementNode { 
-            base: FighterMovementNodeBase {
-                movement: FighterMovement::RunningEast,
-                sprite_name: "Running".to_string(),
-                state_update: |pos,vel,dt| {
-                    pos.x += vel.x * dt;
-                    pos.y += vel.y * dt;
-                },
-                state_enter: |_,vel, facing_east| {
-                    vel.x = RUNNING_SPEED;
-                    facing_east.0 = true;
-                },
-            }, 
-            player_can_enter: |floor_z,pos_z, fighter_movement_stack,event_keytargetset_stack,is_joined_keytargetset| {
-                if !is_joined_keytargetset {return false};
-                let window_time = 0.3;
-
-                let cond1 = pos_z == floor_z;
-                if !cond1 {return false};
-                
-                //check for consecutive keypresses
-                let mut pressed = 0;
-                let mut elements = 0;
-                for timed_keyset in event_keytargetset_stack.0.stack.iter().rev() {
-                    if timed_keyset.duration > window_time || pressed > 1 {break};
-                    if timed_keyset.value.contains(&KeyTarget::RightJustPressed) {pressed += 1};
-                    elements += 1;
-                }
-                let cond2 = pressed > 1;
-                if !cond2 {return false};
-
-                //make sure last movement is idle/walking
-                let mut cond3 = true;
-                if let Some(last_movement) = fighter_movement_stack.0.stack.last() {
-                    if last_movement.value != FighterMovement::Idle && last_movement.value != FighterMovement::WalkingEast {
-                       cond3 = false;
-                    }
-                }
-                if !cond3 {return false};
-
-                //returning true!
-                //remove acted upon events from stack
-                for _ in 0..elements {
-                    event_keytargetset_stack.0.pop();
-                }
-                true
-                },
-            player_can_exit: |_,_,_,movement_request| 
-                {
-                let unallowed_transitions = [
-                    FighterMovement::WalkingEast,
-                    FighterMovement::WalkingNorth,
-                    FighterMovement::WalkingSouth,
-                    FighterMovement::WalkingNorthEast,
-                    FighterMovement::WalkingSouthEast,
-                    FighterMovement::Idle,
-                ];
-
-                for movement in unallowed_transitions {
-                    if movement_request == &movement {return false};
-                }
-                return true;
-                },
-            channel: Some(|full_keytargetset, vel| {
-                    if KeyTargetSet::from([KeyTarget::Up]).is_subset(full_keytargetset) {
-                        vel.y = WALKING_SPEED;
-                    }
-                    if KeyTargetSet::from([KeyTarget::Down]).is_subset(full_keytargetset) {
-                        vel.y = -WALKING_SPEED;
-                    }
-                }),
-            duration_and_fallback: None,
-            hit_boxes: Vec::new(),
-            hurt_boxes: Vec::new(),
-         });
-
-         map.insert_to_event_map(KeyTargetSet::from([KeyTarget::LeftJustPressed]),
-         EventFighterMovementNode { 
-            base: FighterMovementNodeBase {
-                movement: FighterMovement::RunningWest,
-                sprite_name: "Running".to_string(),
-                state_update: |pos,vel,dt| {
-                    pos.x += vel.x * dt;
-                    pos.y += vel.y * dt;
-                },
-                state_enter: |_,vel, facing_east| {
-                    vel.x = -RUNNING_SPEED;
-                    facing_east.0 = false;
-                },
-            }, 
-            player_can_enter: |floor_z,pos_z, fighter_movement_stack,event_keytargetset_stack,is_joined_keytargetset| {
-                if !is_joined_keytargetset {return false};
-                let window_time = 0.3;
-
-                let cond1 = pos_z == floor_z;
-                if !cond1 {return false};
-                
-                //check for consecutive keypresses
-                let mut pressed = 0;
-                let mut elements = 0;
-                for timed_keyset in event_keytargetset_stack.0.stack.iter().rev() {
-                    if timed_keyset.duration > window_time || pressed > 1 {break};
-                    if timed_keyset.value.contains(&KeyTarget::LeftJustPressed) {pressed += 1};
-                    elements += 1;
-                }
-                let cond2 = pressed > 1;
-                if !cond2 {return false};
-
-                //make sure last movement is idle/walking
-                let mut cond3 = true;
-                if let Some(last_movement) = fighter_movement_stack.last() {
-                    if last_movement.value != FighterMovement::Idle && last_movement.value != FighterMovement::WalkingWest {
-                       cond3 = false;
-                    }
-                }
-                if !cond3 {return false};
-
-                //returning true!
-                //remove acted upon events from stack
-                for _ in 0..elements {
-                    event_keytargetset_stack.0.pop();
-                }
-                true
-                },
-            player_can_exit: |_,_,_,movement_request| 
-                {
-                let unallowed_transitions = [
-                    FighterMovement::WalkingWest,
-                    FighterMovement::WalkingNorth,
-                    FighterMovement::WalkingSouth,
-                    FighterMovement::WalkingNorthWest,
-                    FighterMovement::WalkingSouthWest,
-                    FighterMovement::Idle,
-                ];
-
-                for movement in unallowed_transitions {
-                    if movement_request == &movement {return false};
-                }
-                return true;
-                },
-            channel: Some(|full_keytargetset, vel| {
-                    if KeyTargetSet::from([KeyTarget::Up]).is_subset(full_keytargetset) {
-                        vel.y = WALKING_SPEED;
-                    }
-                    if KeyTargetSet::from([KeyTarget::Down]).is_subset(full_keytargetset) {
-                        vel.y = -WALKING_SPEED;
-                    }
-                }),
-            duration_and_fallback: None,
-            hit_boxes: Vec::new(),
-            hurt_boxes: Vec::new(),
-         });
-
-         map.insert_to_persistent_map(KeyTargetSet::from([KeyTarget::Defend]),
-         PersistentFighterMovementNode { 
-             base: FighterMovementNodeBase { 
-                 movement: FighterMovement::Docking,
-                 sprite_name: "Sliding".to_string(),
-                 state_update: |_,_,_| {},
-                 state_enter: |_,vel,_| {
-                     vel.x = 0.0;
-                     vel.y = 0.0;
-                     vel.z = 0.0;
-                     }, 
-             },
-             player_can_enter: |floor_z, position_z| floor_z == position_z,
-             player_can_exit: |_,_,_,_| true,
-             hit_box: HitBox::default(),
-             hurt_box: HitBox::default(), 
-         });
-
-         map.insert_to_event_map(KeyTargetSet::from([KeyTarget::AttackJustPressed]),
-         EventFighterMovementNode { 
-            base: FighterMovementNodeBase {
-                movement: FighterMovement::Slashing,
-                sprite_name: "Slashing".to_string(),
-                state_update: |_,_,_| {},
-                state_enter: |_,vel,_| {
-                    vel.x = 0.0;
-                    vel.y = 0.0;
-                },
-            }, 
-            player_can_enter: |floor_z,pos_z,_,_,joined_keytargetset| {
-                if joined_keytargetset {return false};
-                floor_z == pos_z 
-            },
-            player_can_exit: |_,_,prev_movement_duration ,_| {
-                prev_movement_duration > 0.5
-            },
-            channel: None,
-            duration_and_fallback: None,
-            hit_boxes: Vec::new(),
-            hurt_boxes: Vec::new(),
-         });
-
-         map.insert_to_event_map(KeyTargetSet::from([KeyTarget::AttackJustPressed]),
-         EventFighterMovementNode { 
-            base: FighterMovementNodeBase {
-                movement: FighterMovement::JumpAttack,
-                sprite_name: "AirSlashing".to_string(),
-                state_update: |pos,vel,dt| {
-                    pos.x += vel.x * dt;
-                    pos.y += vel.y * dt;
-                    pos.z += vel.z * dt;
-                    vel.z += GRAVITY * dt;
-                },
-                state_enter: |_,_,_| {},
-            }, 
-            player_can_enter: |floor_z,pos_z,fighter_movement_stack,_,_| {
-                if let Some(durative_movement) = fighter_movement_stack.last() {
-                    if floor_z != pos_z && durative_movement.value == FighterMovement::Jumping{
-                        return true
-                    }
-                }
-                false
-            },
-            player_can_exit: |floor_z,pos_z,_ ,_| {
-                floor_z == pos_z
-            },
-            channel: None,
-            duration_and_fallback: Some(DurationAndFallback {
-                duration: 0.5,
-                fallback: FighterMovement::Jumping,
-                apply_enter_state_fcn: false, 
-            }),
-            hit_boxes: Vec::new(),
-            hurt_boxes: Vec::new(),
-         });
-
-        map
+        Ok(())
     }
 }
 
 #[derive(Resource)]
 pub struct FighterMovementMapCollection(pub HashMap<Fighter, FighterMovementMap>);
-
-impl Default for FighterMovementMapCollection {
-    fn default() -> Self {
-        let mut map = HashMap::new();
-        map.insert(Fighter::IDF, FighterMovementMap::default());
-        map.insert(Fighter::HAMAS, FighterMovementMap::default());
-        Self(map)
-    }
-}
@@ -0,0 +1,114 @@
+use super::components_bundles::*;
+use super::combat::{HitEvent, HitKind};
+use super::utils::*;
+use super::NORTH_WALL_Y;
+
+use bevy::prelude::*;
+
+const CALLOUT_LIFETIME : f32 = 1.0;
+const CALLOUT_HEIGHT : f32 = 72.0; //above the defender's position
+
+#[derive(Component)]
+pub struct ComboCounterData {
+    pub target_entity : Entity,
+}
+
+//shows the combo the target fighter is taking
+#[derive(Bundle)]
+pub struct ComboCounterBundle {
+    text_bundle : Text2dBundle,
+    data : ComboCounterData,
+}
+
+impl ComboCounterBundle {
+    pub fn new(target_entity : Entity, displacement : Vec2, z : f32) -> Self {
+        Self {
+            text_bundle : Text2dBundle {
+                text : Text::from_section("", TextStyle {
+                    font_size : 24.0,
+                    color : Color::YELLOW,
+                    ..default()
+                }).with_alignment(TextAlignment::Center),
+                transform : Transform::from_translation(Vec3::new(displacement.x, displacement.y, z)),
+                ..default()
+            },
+            data : ComboCounterData {
+                target_entity : target_entity,
+            },
+        }
+    }
+}
+
+pub fn update_combo_counters(query_fighter: Query<&FighterCombo>,
+                        mut query_counter: Query<(&ComboCounterData, &mut Text)>) {
+    for (data, mut text) in query_counter.iter_mut() {
+        if let Ok(combo) = query_fighter.get(data.target_entity) {
+            text.sections[0].value = if combo.hits > 1 {
+                format!("{} HITS\n{:.0} DAMAGE", combo.hits, combo.damage)
+            } else {
+                String::new()
+            };
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct CalloutData {
+    pub lifetime : f32, //seconds left until the callout vanishes
+}
+
+//text that pops above a fighter for special hits, fading out
+#[derive(Bundle)]
+pub struct CalloutBundle {
+    text_bundle : Text2dBundle,
+    data : CalloutData,
+}
+
+impl CalloutBundle {
+    pub fn new(text : &str, color : Color, translation : Vec3) -> Self {
+        Self {
+            text_bundle : Text2dBundle {
+                text : Text::from_section(text, TextStyle {
+                    font_size : 28.0,
+                    color : color,
+                    ..default()
+                }).with_alignment(TextAlignment::Center),
+                transform : Transform::from_translation(translation),
+                ..default()
+            },
+            data : CalloutData {
+                lifetime : CALLOUT_LIFETIME,
+            },
+        }
+    }
+}
+
+pub fn spawn_callouts(mut commands: Commands,
+                    mut hit_events: EventReader<HitEvent>,
+                    query_fighter: Query<&FighterPosition>) {
+    for event in hit_events.iter() {
+        let (text, color) = match event.kind {
+            HitKind::CounterHit => ("COUNTER", Color::RED),
+            HitKind::Punish => ("PUNISH", Color::ORANGE),
+            HitKind::Parried => ("PARRY", Color::CYAN),
+            _ => continue,
+        };
+        if let Ok(position) = query_fighter.get(event.defender) {
+            let uvw = project_xyz_2_uvw([position.x, position.y, position.z + CALLOUT_HEIGHT]);
+            commands.spawn(CalloutBundle::new(text, color, Vec3::new(uvw[0], uvw[1], NORTH_WALL_Y)));
+        }
+    }
+}
+
+pub fn update_callouts(mut commands: Commands,
+                    time: Res<Time>,
+                    mut query: Query<(Entity, &mut CalloutData, &mut Text)>) {
+    for (entity, mut data, mut text) in query.iter_mut() {
+        data.lifetime -= time.delta_seconds();
+        if data.lifetime <= 0.0 {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        text.sections[0].style.color.set_a(data.lifetime / CALLOUT_LIFETIME);
+    }
+}
@@ -18,6 +18,25 @@ pub mod fighters_movement_map;
 use fighters_movement_map::*;
 pub mod utils;
 use utils::*;
+pub mod combat;
+use combat::*;
+pub mod debug_overlay;
+use debug_overlay::*;
+pub mod hud;
+use hud::*;
+pub mod projectiles;
+use projectiles::*;
+pub mod throws;
+use throws::*;
+pub mod rounds;
+use rounds::*;
+pub mod effects;
+use effects::*;
+pub mod movement_definitions;
+use movement_definitions::*;
+pub mod movement_validation;
+pub mod movement_dot;
+use movement_dot::*;
 
 //scene
 const CEILING_Z : f32 = -100.0;
@@ -35,9 +54,16 @@ const FIGHTERS : [Fighter;2]= [Fighter::IDF, Fighter::HAMAS];
 const YAML_DATA: &str = include_str!("../assets/assets.yaml");
 
 fn main() {
+    let args : Vec<String> = std::env::args().collect();
+    if let Some(flag_index) = args.iter().position(|arg| arg == EXPORT_DOT_FLAG) {
+        let dirpath = args.get(flag_index + 1).map_or(PathBuf::from("."), PathBuf::from);
+        export_movement_maps_dot(&dirpath);
+        return
+    }
+
     App::new()
     .insert_resource(Msaa::Sample4)
-    .insert_resource(FighterMovementMapCollection::default())
+    .init_resource::<MovementBehaviorRegistry>()
     .add_plugins((EmbeddedAssetPlugin::default(),
                     DefaultPlugins.set(ImagePlugin::default_nearest()),
                     ShapePlugin,
@@ -45,24 +71,49 @@ fn main() {
                     LogDiagnosticsPlugin::default(),
                 ))
     .add_state::<AppState>()
-    .add_systems(OnEnter(AppState::Setup), load_assets)
+    .add_event::<HitEvent>()
+    .add_systems(OnEnter(AppState::Setup), (load_movement_maps, apply_deferred, load_assets).chain())
     .add_systems(Update, check_textures_loaded.run_if(in_state(AppState::Setup)))
     
-    .add_systems(OnEnter(AppState::InGame), setup_game)
+    .add_systems(OnExit(AppState::Setup), setup_game)
+    .add_systems(OnEnter(AppState::RoundOver), announce_round_result)
     .add_systems(
         PreUpdate,
         player_control.run_if(in_state(AppState::InGame)),
     )
     .add_systems(
         Update,
-        (update_state).run_if(in_state(AppState::InGame)),
+        (update_state,
+                update_stamina,
+                resolve_pushboxes,
+                update_juggles,
+                update_fighter_boxes,
+                //once the round is decided, the fighters and projectiles only finish their motion
+                resolve_throws.run_if(in_state(AppState::InGame)),
+                resolve_hits.run_if(in_state(AppState::InGame)),
+                launch_projectiles.run_if(in_state(AppState::InGame)),
+                update_projectiles,
+                resolve_projectile_hits.run_if(in_state(AppState::InGame)),
+                gain_meter,
+                recover_guards,
+                reset_combos,
+                ).chain().run_if(in_state(AppState::InGame).or_else(in_state(AppState::RoundOver))),
     )
+    .add_systems(Update, check_knockouts.after(reset_combos).run_if(in_state(AppState::InGame)))
+    .add_systems(Update, update_round_over.run_if(in_state(AppState::RoundOver)))
     .add_systems(
         PostUpdate,
         (draw_fighters,
-                update_healthbars,
+                draw_projectiles.after(draw_fighters),
+                update_statbars,
                 update_shadows,
-                ).run_if(in_state(AppState::InGame)),
+                update_debug_overlay,
+                update_combo_counters,
+                spawn_callouts,
+                update_callouts,
+                spawn_effects,
+                update_effects.after(draw_fighters),
+                ).run_if(in_state(AppState::InGame).or_else(in_state(AppState::RoundOver))),
     )
     .add_systems(Update, bevy::window::close_on_esc)
     .run();
@@ -73,6 +124,7 @@ pub enum AppState {
     #[default]
     Setup,
     InGame,
+    RoundOver, //input is frozen while the round's result is shown
 }
 
 #[derive(Resource, Deref, DerefMut)]
@@ -82,12 +134,27 @@ struct AnimationTimer(Timer);
 struct AssetLoading {
     fighters_movement_sprites: HashMap<Fighter, HashMap<String, Vec<Handle<Image>>>>,
     background_sprites: Vec<Handle<Image>>,
+    effect_sprites: HashMap<String, Vec<Handle<Image>>>,
 }
 
 struct FighterAnimationHash {
     hashmap: HashMap<String, [usize;2]>,
     atlas_handle: Handle<TextureAtlas>,
 }
+
+impl FighterAnimationHash {
+    //frame of the sprite's animation, counted from the first sprite of sprite_name.
+    //a sprite that hasn't switched to sprite_name's animation yet is at its first frame
+    fn animation_frame(&self, sprite_name : &String, sprite_index : usize) -> usize {
+        let movement_indicies = self.hashmap.get(sprite_name)
+            .expect("sprite_name has no animation");
+        if sprite_index < movement_indicies[0] || sprite_index > movement_indicies[1] {
+            0
+        } else {
+            sprite_index - movement_indicies[0]
+        }
+    }
+}
 #[derive(Resource)]
 struct FightersMovementAnimationIndicies(HashMap<Fighter,FighterAnimationHash>);
 
@@ -99,6 +166,7 @@ fn load_assets(mut commands: Commands,
     let mut assets = AssetLoading {
         fighters_movement_sprites: HashMap::new(),
         background_sprites: Vec::new(),
+        effect_sprites: HashMap::new(),
     };
 
     let yaml : serde_yaml::Mapping = serde_yaml::from_str(YAML_DATA)
@@ -111,7 +179,7 @@ fn load_assets(mut commands: Commands,
     for fighter in FIGHTERS {
         let fighter_movement_graph = figher_movement_map_collection.0.get(&fighter).unwrap();
         let mut fighter_movement_sprites: HashMap<String,Vec<Handle<Image>>> = HashMap::new();
-        for sprite_name in fighter_movement_graph.movement_map.values().map(|x| x.sprite_name()) {
+        for sprite_name in fighter_movement_graph.sprite_names() {
             let mut sprites_vec: Vec<Handle<Image>> = Vec::new();
             let dirpath = PathBuf::from("textures").join(fighter.to_string()).join(sprite_name);
             
@@ -129,6 +197,28 @@ fn load_assets(mut commands: Commands,
         }
         assets.fighters_movement_sprites.insert(fighter, fighter_movement_sprites);
     }
+
+    //load effect sprites, every folder under textures/effects is one effect
+    let effects_dirpath = PathBuf::from("textures").join("effects");
+    for (key, value) in yaml.iter() {
+        let dirpath = PathBuf::from(key.as_str().expect("yaml key is not a string"));
+        if dirpath.parent() != Some(effects_dirpath.as_path()) {continue};
+        let effect_name = dirpath.file_name().unwrap().to_str()
+            .expect("dirpath has to contain only UTF-8 validity").to_string();
+        let mut sprites_vec: Vec<Handle<Image>> = Vec::new();
+        for entry in value.as_sequence().expect("yaml value is not a sequence") {
+            let filename = entry.as_str().unwrap();
+            let fullfilename = format!("{}/{}", dirpath.to_str().unwrap(), filename);
+            sprites_vec.push(asset_server.load(fullfilename));
+        }
+        assets.effect_sprites.insert(effect_name, sprites_vec);
+    }
+    //a missing effect is not fatal, its hits are just drawn without one
+    for effect_name in EFFECT_NAMES {
+        if !assets.effect_sprites.contains_key(effect_name) {
+            warn!("no sprites for effect {} under {} in assets.yaml", effect_name, effects_dirpath.display());
+        }
+    }
     commands.insert_resource(assets);
 }
 
@@ -151,19 +241,18 @@ fn check_textures_loaded(
         }
     }
     
-    for (_, movement_sprites_handles) in asset_loading.fighters_movement_sprites.iter() {
-        for (_, sprites_handles) in movement_sprites_handles.iter() {
-            for sprite_handle in sprites_handles.iter() {
-                let sprite_load_state = asset_server.get_load_state(sprite_handle);
-                match sprite_load_state {
-                    LoadState::Loaded => {}
-                    LoadState::NotLoaded | LoadState::Loading => {return;}
-                    LoadState::Failed => {
-                        panic!("Failed to load sprite");
-                    }
-                    _ => {
-                        panic!("Unexpected load state");
-                    }
+    let fighters_sprites_handles = asset_loading.fighters_movement_sprites.values().flat_map(|x| x.values());
+    for sprites_handles in fighters_sprites_handles.chain(asset_loading.effect_sprites.values()) {
+        for sprite_handle in sprites_handles.iter() {
+            let sprite_load_state = asset_server.get_load_state(sprite_handle);
+            match sprite_load_state {
+                LoadState::Loaded => {}
+                LoadState::NotLoaded | LoadState::Loading => {return;}
+                LoadState::Failed => {
+                    panic!("Failed to load sprite");
+                }
+                _ => {
+                    panic!("Unexpected load state");
                 }
             }
         }
@@ -172,11 +261,30 @@ fn check_textures_loaded(
     info!("all assets loaded")
 }
 
+//pack the sprites of every animation into one texture atlas
+fn build_animation_hash(sprites_handles_map : &HashMap<String, Vec<Handle<Image>>>,
+                        textures : &mut Assets<Image>,
+                        texture_atlases : &mut Assets<TextureAtlas>) -> FighterAnimationHash {
+    let mut atlas_builder: TileAtlasBuilder = TileAtlasBuilder::default();
+    let mut movement_indicies: HashMap<String, [usize;2]> = HashMap::new();
+    let mut index : usize = 0;
+    for (movement_name, sprites_handles) in sprites_handles_map.iter() {
+        for sprite_handle in sprites_handles.iter() {
+            atlas_builder.add_texture(sprite_handle.clone(), textures.get(&sprite_handle).unwrap()).unwrap();
+        }
+        movement_indicies.insert(movement_name.clone(), [index, index + sprites_handles.len()-1]);
+        index += sprites_handles.len();
+    }
+    let texture_atlas_handle = texture_atlases.add(atlas_builder.finish(textures).unwrap());
+    FighterAnimationHash{hashmap : movement_indicies, atlas_handle : texture_atlas_handle}
+}
+
 fn setup_game(
     mut commands: Commands,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
     mut textures: ResMut<Assets<Image>>,
     asset_loading: Res<AssetLoading>,
+    figher_movement_map_collection: Res<FighterMovementMapCollection>,
     mut windows: Query<&mut Window>) {
     
     commands.spawn(Camera2dBundle::default());
@@ -197,21 +305,16 @@ fn setup_game(
     //build texture atlases for all fighters
     let mut fighters_movement_animation_indicies = FightersMovementAnimationIndicies(HashMap::new());
     for (fighter, movement_sprites_handles) in asset_loading.fighters_movement_sprites.iter() {
-        let mut atlas_builder: TileAtlasBuilder = TileAtlasBuilder::default();
-        let mut movement_indicies: HashMap<String, [usize;2]> = HashMap::new();
-        let mut index : usize = 0;
-        for (movement_name, sprites_handles) in movement_sprites_handles.iter() {
-            for sprite_handle in sprites_handles.iter() {
-                atlas_builder.add_texture(sprite_handle.clone(), textures.get(&sprite_handle).unwrap()).unwrap();
-            }
-            movement_indicies.insert(movement_name.clone(), [index, index + sprites_handles.len()-1]);
-            index += sprites_handles.len();
-        }
-        let texture_atlas_handle = texture_atlases.add(atlas_builder.finish(&mut textures).unwrap());
-        let fighter_animation_hash = FighterAnimationHash{hashmap : movement_indicies, atlas_handle : texture_atlas_handle};
+        let fighter_animation_hash = build_animation_hash(movement_sprites_handles, &mut textures, &mut texture_atlases);
         fighters_movement_animation_indicies.0.insert(*fighter, fighter_animation_hash);
     }
 
+    //effects share one texture atlas
+    if !asset_loading.effect_sprites.is_empty() {
+        commands.insert_resource(EffectsAnimation(
+            build_animation_hash(&asset_loading.effect_sprites, &mut textures, &mut texture_atlases)));
+    }
+
     let mut spawn_fighter = |player : Player,
                                             player_controls : PlayerControls,
                                             fighter : Fighter,
@@ -226,22 +329,37 @@ fn setup_game(
         ..default()};
     let mut movement_stack = FighterMovementStack::new(10);
         movement_stack.push(FighterMovement::InAir);
-    let fighter_id = commands.spawn(ControlledFighterBundle{
+    let round_start = RoundStart{position : (&position).into(), facing_east : facing_east};
+    let stats = figher_movement_map_collection.0.get(&fighter).unwrap().stats;
+    let fighter_id = commands.spawn((ControlledFighterBundle{
                                         player : player,
                                         controls : player_controls,
                                         fighter_bundle : FighterBundle {
                                             fighter: fighter,
                                             hitbox: FighterHitBox::default(),
                                             hurtbox: FighterHurtBox::default(),
-                                            health : FighterHealth{current : 100.0, max : 100.0},
+                                            pushbox: FighterPushBox{dx : 24.0, dy : 16.0},
+                                            health : FighterHealth{current : stats.health, max : stats.health},
+                                            combo : FighterCombo::default(),
+                                            juggle : FighterJuggle::default(),
+                                            guard : FighterGuard{current : 50.0, max : 50.0},
+                                            stamina : FighterStamina::new(stats.stamina_drain_rate, stats.stamina_recovery_rate),
+                                            meter : FighterMeter{current : 0.0, max : METER_SEGMENTS as f32 * METER_SEGMENT},
+                                            parry : FighterParry{window : stats.parry_window},
                                             position : position,
                                             velocity : FighterVelocity{x : 0.0, y :0.0, z :0.0},
                                             facing_east : FacingEast(facing_east),
+                                            stun : FighterStun(0.0),
+                                            hitstop : FighterHitstop(0.0),
                                             movement_stack : movement_stack,
                                             event_keytargetset_stack : KeyTargetSetStack::new(10, 0.5),
                                             sprite : sprite_sheet_bundle,
                                     }
-    }).id();
+    },
+    ProjectileLauncher::default(),
+    FighterThrown::default(),
+    FighterPhase::default(),
+    round_start,)).id();
 
     // shadow
     commands.spawn(ShadowBundle::new(Vec2::new(20.0,10.0),
@@ -254,6 +372,8 @@ fn setup_game(
                                         -32.0
                                     ));
 
+    //debug overlay
+    commands.spawn(DebugOverlayBundle::new(fighter_id, NORTH_WALL_Y));
 
     //health bar
     let (healthbar_green_bundle,healthbar_red_bundle) = StatBarBundle::new_with_emptycolor(Color::rgb(0.0, 1.0, 0.0),
@@ -266,9 +386,78 @@ fn setup_game(
                                         health_bar_reverse,
                                         false,
                                         fighter_id,
+                                        FighterStat::Health,
                                         0.0);
     let healthbar_green_id = commands.spawn(healthbar_green_bundle).id();
     commands.spawn(healthbar_red_bundle).set_parent(healthbar_green_id);
+
+    //guard bar, under the health bar
+    let (guardbar_blue_bundle,guardbar_gray_bundle) = StatBarBundle::new_with_emptycolor(Color::rgb(0.2, 0.6, 1.0),
+                            Color::rgb(0.3, 0.3, 0.3),
+                                        window.width()/3.0,
+                                        window.height()/40.0,
+                                        Vec2::new(
+                                            health_bar_x,
+                                            -window.height()/2.0 + window.height() * 0.95 - window.height()/20.0 * 1.2),
+                                        health_bar_reverse,
+                                        false,
+                                        fighter_id,
+                                        FighterStat::Guard,
+                                        0.0);
+    let guardbar_blue_id = commands.spawn(guardbar_blue_bundle).id();
+    commands.spawn(guardbar_gray_bundle).set_parent(guardbar_blue_id);
+
+    //stamina bar, under the guard bar
+    let (staminabar_green_bundle,staminabar_gray_bundle) = StatBarBundle::new_with_emptycolor(Color::rgb(0.6, 1.0, 0.2),
+                            Color::rgb(0.3, 0.3, 0.3),
+                                        window.width()/3.0,
+                                        window.height()/40.0,
+                                        Vec2::new(
+                                            health_bar_x,
+                                            -window.height()/2.0 + window.height() * 0.95 - window.height()/20.0 * 1.2 - window.height()/40.0 * 1.2),
+                                        health_bar_reverse,
+                                        false,
+                                        fighter_id,
+                                        FighterStat::Stamina,
+                                        0.0);
+    let staminabar_green_id = commands.spawn(staminabar_green_bundle).id();
+    commands.spawn(staminabar_gray_bundle).set_parent(staminabar_green_id);
+
+    //meter bar, at the bottom of the screen and split into its segments
+    let (meterbar_yellow_bundle,meterbar_gray_bundle) = StatBarBundle::new_with_emptycolor(Color::rgb(1.0, 0.8, 0.0),
+                            Color::rgb(0.3, 0.3, 0.3),
+                                        window.width()/4.0,
+                                        window.height()/40.0,
+                                        Vec2::new(
+                                            health_bar_x,
+                                            -window.height()/2.0 + window.height() * 0.08),
+                                        health_bar_reverse,
+                                        false,
+                                        fighter_id,
+                                        FighterStat::Meter,
+                                        0.0);
+    let meterbar_yellow_id = commands.spawn(meterbar_yellow_bundle).id();
+    commands.spawn(meterbar_gray_bundle).set_parent(meterbar_yellow_id);
+    for divider_bundle in StatBarDividerBundle::new(Color::BLACK,
+                                        health_bar_reverse,
+                                        window.width()/4.0,
+                                        window.height()/40.0,
+                                        METER_SEGMENTS,
+                                        0.0) {
+        commands.spawn(divider_bundle).set_parent(meterbar_yellow_id);
+    }
+
+    //combo counter, under the bars towards the middle of the screen
+    let combo_counter_x = if health_bar_reverse {
+        health_bar_x - window.width()/6.0
+    } else {
+        health_bar_x + window.width()/6.0
+    };
+    commands.spawn(ComboCounterBundle::new(fighter_id,
+                                        Vec2::new(
+                                            combo_counter_x,
+                                            -window.height()/2.0 + window.height() * 0.8),
+                                        0.0));
     };
 
     spawn_fighter(Player::Player1,
@@ -308,23 +497,21 @@ fn enter_requested_node<T>(request_movement_nodes : Vec<&Arc<T>>,
     movement_stack : &mut FighterMovementStack,
     position : &mut FighterPosition,
     velocity: &mut FighterVelocity,
-    facing_east: &mut FacingEast,) -> Option<FighterMovement>
+    facing_east: &mut FacingEast,
+    stats: &FighterStats,) -> Option<FighterMovement>
     where T: FighterMovementNodeTrait {
-    match request_movement_nodes.len() {
-        0 => None,
-        1 => {
-            let new_movement_node: &&Arc<T> = &request_movement_nodes[0];
-            movement_stack.push(new_movement_node.movement());
-            new_movement_node.state_enter(position, velocity, facing_east);
-            Some(new_movement_node.movement())
-        }
-        _ => {
-            let culprit_movements = request_movement_nodes.iter()
-                        .map(|x| x.movement())
-                        .collect::<Vec<_>>();
-            panic!("two or more movements. the culprits are {:#?}", culprit_movements)
-        },   
+    //validate_movement_map rules out overlaps at load time, ones only some input histories cause
+    //are logged and the first movement is entered
+    let new_movement_node = request_movement_nodes.first()?;
+    if request_movement_nodes.len() > 1 {
+        let culprit_movements = request_movement_nodes.iter()
+                    .map(|x| x.movement())
+                    .collect::<Vec<_>>();
+        error!("two or more movements. the culprits are {:?}", culprit_movements);
     }
+    movement_stack.push(new_movement_node.movement());
+    new_movement_node.state_enter(position, velocity, facing_east, stats);
+    Some(new_movement_node.movement())
 }
 
 //each variant might have a different signature for the exit
@@ -334,7 +521,8 @@ fn can_exit_node<T>(request_movement_node : &Arc<T>,
     where T: FighterMovementNodeTrait {
     match current_movement_node {
         FighterMovementNode::EventTriggered(node) => {
-            (node.player_can_exit)(FLOOR_Z, pos_z, current_movement_duration, &request_movement_node.movement())
+            current_movement_duration >= node.min_duration
+                && (node.player_can_exit)(FLOOR_Z, pos_z, current_movement_duration, &request_movement_node.movement())
         }
         FighterMovementNode::Persistent(node) => {
             (node.player_can_exit)(FLOOR_Z, pos_z, current_movement_duration, &request_movement_node.movement())
@@ -343,8 +531,17 @@ fn can_exit_node<T>(request_movement_node : &Arc<T>,
     }
 }
 
+fn spend_meter(fighter_map : &FighterMovementMap, movement : &FighterMovement, meter : &mut FighterMeter) {
+    let node = fighter_map.get_event_node_by_movement(movement)
+        .expect("event movement wasn't found in fighter_map");
+    meter.current = (meter.current - node.meter_cost).max(0.0);
+}
+
 fn player_control(mut query: Query<(&Fighter,
                                     &PlayerControls,
+                                    &FighterStun,
+                                    &FighterHitstop,
+                                    &mut FighterMeter,
                                     &mut KeyTargetSetStack,
                                     &mut FighterMovementStack,
                                     &mut FighterPosition,
@@ -358,6 +555,9 @@ fn player_control(mut query: Query<(&Fighter,
 
     for (fighter,
         player_controls,
+        stun,
+        hitstop,
+        mut meter,
         mut event_keytargetset_stack,
         mut movement_stack,
         mut position,
@@ -366,11 +566,16 @@ fn player_control(mut query: Query<(&Fighter,
 
         let fighter_map = figher_movement_map_collection.0.get(&fighter).unwrap();
 
-        //update event_keytargetset_stack and movement stack
-        movement_stack.0.update(time.delta_seconds());
+        //update event_keytargetset_stack and movement stack.
+        //frozen fighters keep buffering input, but their movement doesn't progress
         let event_keytargetset = player_controls.into_event_keytargetset(&keyboard_input);
         event_keytargetset_stack.0.update(time.delta_seconds());
         event_keytargetset_stack.0.push(event_keytargetset.clone());
+        if hitstop.0 > 0.0 {continue};
+        movement_stack.0.update(time.delta_seconds());
+
+        //stunned fighters can't be controlled
+        if stun.0 > 0.0 {continue};
 
         let current_durative_movement = movement_stack.last()
             .expect("movement_stack is empty").clone();
@@ -381,12 +586,14 @@ fn player_control(mut query: Query<(&Fighter,
         //try an event triggered from current event_keytargetset
         if let Some(movement_nodes) = fighter_map.event_map.get(&event_keytargetset) {
             let filtered_request_nodes = movement_nodes.iter().filter(|request_movement_node| {
-                let can_enter = (request_movement_node.player_can_enter)(FLOOR_Z, position.z, &movement_stack, &mut event_keytargetset_stack, false);
+                let can_enter = (request_movement_node.player_can_enter)(FLOOR_Z, position.z, &movement_stack, &mut event_keytargetset_stack, false)
+                    && request_movement_node.meter_cost <= meter.current;
                 let can_exit = can_exit_node(request_movement_node, current_movement_node, position.z, current_durative_movement.duration);
                 can_enter & can_exit
                 }).collect::<Vec<_>>();
-            if let Some(_) = enter_requested_node(filtered_request_nodes,
-                 &mut movement_stack, &mut position, &mut velocity, &mut facing_east) {
+            if let Some(movement) = enter_requested_node(filtered_request_nodes,
+                 &mut movement_stack, &mut position, &mut velocity, &mut facing_east, &fighter_map.stats) {
+                spend_meter(fighter_map, &movement, &mut meter);
                 continue
             };
         }
@@ -395,12 +602,14 @@ fn player_control(mut query: Query<(&Fighter,
         let joined_event_keytargetset = event_keytargetset_stack.join();
         if let Some(movement_nodes) = fighter_map.event_map.get(&joined_event_keytargetset) {
             let filtered_request_nodes = movement_nodes.iter().filter(|request_movement_node| {
-                let can_enter = (request_movement_node.player_can_enter)(FLOOR_Z, position.z, &movement_stack, &mut event_keytargetset_stack, true);
+                let can_enter = (request_movement_node.player_can_enter)(FLOOR_Z, position.z, &movement_stack, &mut event_keytargetset_stack, true)
+                    && request_movement_node.meter_cost <= meter.current;
                 let can_exit = can_exit_node(request_movement_node, current_movement_node, position.z, current_durative_movement.duration);
                 can_enter & can_exit
                 }).collect::<Vec<_>>();
-            if let Some(_) = enter_requested_node(filtered_request_nodes,
-                 &mut movement_stack, &mut position, &mut velocity, &mut facing_east) {
+            if let Some(movement) = enter_requested_node(filtered_request_nodes,
+                 &mut movement_stack, &mut position, &mut velocity, &mut facing_east, &fighter_map.stats) {
+                spend_meter(fighter_map, &movement, &mut meter);
                 continue
             };
         }        
@@ -420,7 +629,7 @@ fn player_control(mut query: Query<(&Fighter,
             
             //
             if let Some(_) = enter_requested_node(filtered_request_nodes,
-                &mut movement_stack,&mut position,&mut velocity, &mut facing_east) {
+                &mut movement_stack,&mut position,&mut velocity, &mut facing_east, &fighter_map.stats) {
                 continue
             };
         }
@@ -434,7 +643,7 @@ fn player_control(mut query: Query<(&Fighter,
 
             if can_enter && can_exit {
                 movement_stack.0.push(FighterMovement::Idle);
-                (idle_node.base.state_enter)(&mut position, &mut velocity, &mut facing_east);
+                idle_node.state_enter(&mut position, &mut velocity, &mut facing_east, &fighter_map.stats);
                 continue
             }
         }
@@ -442,18 +651,43 @@ fn player_control(mut query: Query<(&Fighter,
         //if all else failed, see if its a channel
         let full_keytargetset = player_controls.into_full_keytargetset(&keyboard_input);
         if let FighterMovementNode::EventTriggered(node) = current_movement_node {
-            if let Some(channel) = node.channel {
-                channel(&full_keytargetset ,&mut velocity)
+            if let Some(channel) = &node.channel {
+                channel(&full_keytargetset ,&mut velocity, &fighter_map.stats)
             }
         }
     }  
 }
+fn clamp_to_stage(position : &mut FighterPosition) {
+    position.x = position.x.clamp(WEST_WALL_X,EAST_WALL_X);
+    position.y = position.y.clamp(SOUTH_WALL_Y, NORTH_WALL_Y);
+    position.z = position.z.clamp(FLOOR_Z, CEILING_Z);
+}
+
+//fighters knocked past a wall or the floor fast enough bounce back with reduced velocity.
+//returns the bounce movement to enter
+fn bounce_off_bounds(position : &FighterPosition, velocity : &mut FighterVelocity) -> Option<FighterMovement> {
+    let into_west_wall = position.x < WEST_WALL_X && velocity.x < -WALL_BOUNCE_SPEED;
+    let into_east_wall = position.x > EAST_WALL_X && velocity.x > WALL_BOUNCE_SPEED;
+    if into_west_wall || into_east_wall {
+        velocity.x = -velocity.x * WALL_BOUNCE_RESTITUTION;
+        return Some(FighterMovement::WallBounce)
+    }
+    if position.z < FLOOR_Z && velocity.z < -GROUND_BOUNCE_SPEED {
+        velocity.z = -velocity.z * GROUND_BOUNCE_RESTITUTION;
+        return Some(FighterMovement::GroundBounce)
+    }
+    None
+}
+
 fn update_state(mut query: Query<(&Fighter,
                                     &mut FighterPosition,
                                     &mut FighterVelocity,
+                                    &mut FacingEast,
                                     &mut FighterHealth,
                                     &FighterHitBox,
                                     &FighterHurtBox,
+                                    &mut FighterStun,
+                                    &mut FighterHitstop,
                                     &mut FighterMovementStack,)>,
                                     time: Res<Time>,
                                     figher_movement_map_collection: Res<FighterMovementMapCollection>,) {
@@ -462,11 +696,24 @@ fn update_state(mut query: Query<(&Fighter,
     for (fighter,
         mut position,
         mut velocity,
+        mut facing_east,
         mut health,
         hitbox,
         hurtbox,
+        mut stun,
+        mut hitstop,
         mut movement_stack) in query.iter_mut() {
 
+        if hitstop.0 > 0.0 {
+            hitstop.0 = (hitstop.0 - dt).max(0.0);
+            continue;
+        }
+
+        //stun only wears off on the floor, airborne fighters stay helpless until they land
+        if position.z == FLOOR_Z {
+            stun.0 = (stun.0 - dt).max(0.0);
+        }
+
         let fighter_map = figher_movement_map_collection.0.get(&fighter)
             .expect("fighter does not exist in the movement graph");
         if let Some(current_durative_movement) = movement_stack.last() {
@@ -482,20 +729,157 @@ fn update_state(mut query: Query<(&Fighter,
                 }
             }
 
-            movement_node.state_update(&mut position, &mut velocity, dt);
-            position.x = position.x.clamp(WEST_WALL_X,EAST_WALL_X);
-            position.y = position.y.clamp(SOUTH_WALL_Y, NORTH_WALL_Y);
-            position.z = position.z.clamp(FLOOR_Z, CEILING_Z);
+            movement_node.state_update(&mut position, &mut velocity, dt, &fighter_map.stats);
+            let bounce = match movement_node {
+                FighterMovementNode::Uncontrollable(node) if node.bounces => {
+                    bounce_off_bounds(&position, &mut velocity)
+                },
+                _ => None,
+            };
+            clamp_to_stage(&mut position);
+
+            if let Some(bounce) = bounce {
+                movement_stack.push(bounce);
+                continue;
+            }
+
+            if let FighterMovementNode::Uncontrollable(node) = movement_node {
+                if let Some(landing_fallback) = node.landing_fallback {
+                    if position.z == FLOOR_Z {
+                        movement_stack.push(landing_fallback);
+                        fighter_map.get_node_by_movement(&landing_fallback)
+                            .expect("landing fallback wasn't found in fighter_map")
+                            .state_enter(&mut position, &mut velocity, &mut facing_east, &fighter_map.stats);
+                    }
+                }
+            }
+        }
+    }
+}
+
+//push overlapping fighters apart along the axis they overlap the least, each giving way half.
+//a fighter pinned to a wall can't give way, so the other one takes the rest of the push
+//fighters jumping over each other switch sides, so only grounded fighters are pushed apart
+fn push_apart(pushbox_a : &FighterPushBox, facing_east_a : &FacingEast, position_a : &mut FighterPosition,
+            pushbox_b : &FighterPushBox, position_b : &mut FighterPosition) {
+    if position_a.z > FLOOR_Z || position_b.z > FLOOR_Z {return};
+
+    let overlap_x = (pushbox_a.dx + pushbox_b.dx)/2.0 - (position_b.x - position_a.x).abs();
+    let overlap_y = (pushbox_a.dy + pushbox_b.dy)/2.0 - (position_b.y - position_a.y).abs();
+    if overlap_x <= 0.0 || overlap_y <= 0.0 {return};
+
+    if overlap_x < overlap_y {
+        //fighters standing on each other are split by their facing
+        let sign = if position_b.x != position_a.x {
+            (position_b.x - position_a.x).signum()
+        } else if facing_east_a.0 {1.0} else {-1.0};
+        position_a.x -= sign * overlap_x/2.0;
+        position_b.x += sign * overlap_x/2.0;
+        clamp_to_stage(position_a);
+        clamp_to_stage(position_b);
+
+        let overlap_x = (pushbox_a.dx + pushbox_b.dx)/2.0 - (position_b.x - position_a.x).abs();
+        if overlap_x > 0.0 {
+            if position_a.x == WEST_WALL_X || position_a.x == EAST_WALL_X {
+                position_b.x += sign * overlap_x;
+            } else {
+                position_a.x -= sign * overlap_x;
+            }
+            clamp_to_stage(position_a);
+            clamp_to_stage(position_b);
+        }
+    } else {
+        let sign = if position_b.y >= position_a.y {1.0} else {-1.0};
+        position_a.y -= sign * overlap_y/2.0;
+        position_b.y += sign * overlap_y/2.0;
+        clamp_to_stage(position_a);
+        clamp_to_stage(position_b);
+
+        let overlap_y = (pushbox_a.dy + pushbox_b.dy)/2.0 - (position_b.y - position_a.y).abs();
+        if overlap_y > 0.0 {
+            if position_a.y == SOUTH_WALL_Y || position_a.y == NORTH_WALL_Y {
+                position_b.y += sign * overlap_y;
+            } else {
+                position_a.y -= sign * overlap_y;
+            }
+            clamp_to_stage(position_a);
+            clamp_to_stage(position_b);
         }
     }
 }
 
-fn update_healthbars(fighter_health_query: Query<&FighterHealth>,
+fn resolve_pushboxes(mut query: Query<(&FighterPushBox, &FacingEast, &mut FighterPosition)>) {
+    let mut combinations = query.iter_combinations_mut();
+    while let Some([(pushbox_a, facing_east_a, mut position_a),
+                    (pushbox_b, _, mut position_b)]) = combinations.fetch_next() {
+        push_apart(pushbox_a, facing_east_a, &mut position_a, pushbox_b, &mut position_b);
+    }
+}
+
+//running and dashing drain stamina, walking and idling recover it.
+//fighters out of stamina are slowed back to walking
+fn update_stamina(mut query: Query<(&Fighter,
+                                    &FighterHitstop,
+                                    &mut FighterStamina,
+                                    &mut FighterMovementStack,
+                                    &mut FighterPosition,
+                                    &mut FighterVelocity,
+                                    &mut FacingEast,)>,
+                                    time: Res<Time>,
+                                    figher_movement_map_collection: Res<FighterMovementMapCollection>,) {
+    let dt = time.delta_seconds();
+    for (fighter,
+        hitstop,
+        mut stamina,
+        mut movement_stack,
+        mut position,
+        mut velocity,
+        mut facing_east) in query.iter_mut() {
+        if hitstop.0 > 0.0 {continue};
+        let Some(current_durative_movement) = movement_stack.last() else {continue};
+
+        let exhausted_movement = match current_durative_movement.value {
+            FighterMovement::RunningEast => FighterMovement::WalkingEast,
+            FighterMovement::RunningWest => FighterMovement::WalkingWest,
+            FighterMovement::Backdash => FighterMovement::Idle,
+            FighterMovement::Idle
+            | FighterMovement::WalkingEast
+            | FighterMovement::WalkingWest
+            | FighterMovement::WalkingNorth
+            | FighterMovement::WalkingSouth
+            | FighterMovement::WalkingNorthEast
+            | FighterMovement::WalkingNorthWest
+            | FighterMovement::WalkingSouthEast
+            | FighterMovement::WalkingSouthWest => {
+                stamina.current = (stamina.current + stamina.recovery_rate * dt).min(stamina.max);
+                continue;
+            },
+            _ => continue,
+        };
+
+        stamina.current = (stamina.current - stamina.drain_rate * dt).max(0.0);
+        if stamina.current == 0.0 {
+            let fighter_map = figher_movement_map_collection.0.get(&fighter)
+                .expect("fighter does not exist in the movement graph");
+            movement_stack.push(exhausted_movement);
+            fighter_map.get_node_by_movement(&exhausted_movement)
+                .expect("movement wasn't found in fighter_map")
+                .state_enter(&mut position, &mut velocity, &mut facing_east, &fighter_map.stats);
+        }
+    }
+}
+
+fn update_statbars(fighter_stats_query: Query<(&FighterHealth, &FighterGuard, &FighterMeter, &FighterStamina)>,
                         mut statbar_query : Query<(&StatBarData, &mut Sprite)>) {
     for (data,
          mut sprite) in statbar_query.iter_mut() {
-        if let Ok(health) = fighter_health_query.get(data.target_entity) {
-            let value = health.current/health.max;
+        if let Ok((health, guard, meter, stamina)) = fighter_stats_query.get(data.target_entity) {
+            let value = match data.stat {
+                FighterStat::Health => health.current/health.max,
+                FighterStat::Guard => guard.current/guard.max,
+                FighterStat::Meter => meter.current/meter.max,
+                FighterStat::Stamina => stamina.current/stamina.max,
+            };
             sprite.rect = Some(Rect {
                 min :  Vec2::new(0.0, 0.0),
                 max : Vec2::new(data.max_length * value, data.thickness),
@@ -504,13 +888,18 @@ fn update_healthbars(fighter_health_query: Query<&FighterHealth>,
     }
 }
 
-fn update_shadows(query_fighter_position: Query<&FighterPosition>,
-                mut query_fighter_shadows: Query<(&mut Transform, &mut ShadowData)>) {
-    for (mut transform,
+fn update_shadows(mut commands: Commands,
+                query_fighter_position: Query<&FighterPosition>,
+                mut query_fighter_shadows: Query<(Entity, &mut Transform, &mut ShadowData)>) {
+    for (entity,
+        mut transform,
         shadow) in query_fighter_shadows.iter_mut() {
         if let Ok(position) = query_fighter_position.get(shadow.target_entity) {
             let uvw = project_xyz_2_uvw([position.x, position.y, FLOOR_Z + shadow.height_offset]);
             transform.translation = Vec3::new(uvw[0], uvw[1], shadow.z);
+        } else {
+            //the target despawned, e.g. a projectile that expired
+            commands.entity(entity).despawn();
         }
     }
 }
@@ -522,6 +911,7 @@ fn draw_fighters(time: Res<Time>,
                 mut query: Query<(&Fighter,
                                 &FighterMovementStack,
                                 &FighterPosition,
+                                &FighterHitstop,
                                 Ref<FacingEast>,
                                 &mut TextureAtlasSprite,
                                 &mut Transform,)>) {
@@ -530,6 +920,7 @@ fn draw_fighters(time: Res<Time>,
     for (fighter,
         movement_stack,
         position,
+        hitstop,
         facing_right,
         mut sprite,
         mut transform) in query.iter_mut() {
@@ -539,7 +930,7 @@ fn draw_fighters(time: Res<Time>,
                                                                 .get_node_by_movement(&last_durative_movement.value).unwrap();
             let sprite_name = movement_node.sprite_name();
 
-            if animation_timer.just_finished() {
+            if animation_timer.just_finished() && hitstop.0 == 0.0 {
                 let movement_indicies = fighters_movement_animation_indicies.0.get(&fighter).unwrap()
                                                                                     .hashmap.get(sprite_name).unwrap();
                 if sprite.index < movement_indicies[0] || sprite.index > movement_indicies[1]-1 {
@@ -557,4 +948,84 @@ fn draw_fighters(time: Res<Time>,
             }
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use movement_definitions::shipped_movement_maps;
+
+    //a fighter jumping out of a walk east over a standing opponent lands on its other side,
+    //moved by the shipped WalkingEast and Jumping nodes
+    #[test]
+    fn jump_crosses_over_standing_opponent() {
+        let collection = shipped_movement_maps();
+        for fighter in FIGHTERS {
+            let fighter_map = collection.0.get(&fighter).unwrap();
+            let stats = &fighter_map.stats;
+            let pushbox = FighterPushBox{dx : 24.0, dy : 16.0};
+            let mut jumper = FighterPosition{x : 0.0, y : 0.0, z : FLOOR_Z};
+            let mut opponent = FighterPosition{x : 20.0, y : 0.0, z : FLOOR_Z};
+            let mut velocity = FighterVelocity{x : 0.0, y : 0.0, z : 0.0};
+            let mut facing_east = FacingEast(true);
+            for movement in [FighterMovement::WalkingEast, FighterMovement::Jumping] {
+                fighter_map.get_node_by_movement(&movement).unwrap()
+                    .state_enter(&mut jumper, &mut velocity, &mut facing_east, stats);
+            }
+            let jumping = fighter_map.get_node_by_movement(&FighterMovement::Jumping).unwrap();
+            let dt = 1.0/60.0;
+            for _ in 0..600 {
+                jumping.state_update(&mut jumper, &mut velocity, dt, stats);
+                clamp_to_stage(&mut jumper);
+                push_apart(&pushbox, &facing_east, &mut jumper, &pushbox, &mut opponent);
+                if jumper.z == FLOOR_Z {break};
+            }
+            assert_eq!(jumper.z, FLOOR_Z, "{} never landed", fighter);
+            assert!(jumper.x > opponent.x, "{} landed at {} behind the opponent at {}", fighter, jumper.x, opponent.x);
+        }
+    }
+
+    //the defender knocked back by one of HAMAS's moves from x, as juggled with a single hit.
+    //returns the bounce the knockback ends in, if any
+    fn knocked_into_bounds(defender : Fighter, movement : FighterMovement, frame : usize, x : f32) -> Option<FighterMovement> {
+        let collection = shipped_movement_maps();
+        let hit = *collection.0.get(&Fighter::HAMAS).unwrap()
+            .get_node_by_movement(&movement).unwrap()
+            .framed_hit_box(frame).unwrap();
+        let defender_map = collection.0.get(&defender).unwrap();
+        let juggled = defender_map.get_node_by_movement(&FighterMovement::Juggled).unwrap();
+        let stats = &defender_map.stats;
+        let mut position = FighterPosition{x : x, y : 0.0, z : FLOOR_Z};
+        let mut velocity = FighterVelocity{x : hit.knockback[0]/stats.weight, y : 0.0, z : hit.knockback[1]/stats.weight};
+        let dt = 1.0/60.0;
+        for _ in 0..600 {
+            juggled.state_update(&mut position, &mut velocity, dt, stats);
+            let bounce = bounce_off_bounds(&position, &mut velocity);
+            clamp_to_stage(&mut position);
+            if bounce.is_some() || position.z == FLOOR_Z {return bounce};
+            velocity.z += stats.gravity * JUGGLE_GRAVITY_SCALING * dt;
+        }
+        None
+    }
+
+    #[test]
+    fn shoulder_charge_bounces_off_the_wall() {
+        for defender in FIGHTERS {
+            assert_eq!(knocked_into_bounds(defender, FighterMovement::ShoulderCharge, 1, EAST_WALL_X - 10.0),
+                Some(FighterMovement::WallBounce), "{}", defender);
+        }
+    }
+
+    #[test]
+    fn uppercut_bounces_off_the_floor() {
+        assert_eq!(knocked_into_bounds(Fighter::IDF, FighterMovement::Uppercut, 0, 0.0), Some(FighterMovement::GroundBounce));
+    }
+
+    #[test]
+    fn grounded_fighters_are_pushed_apart() {
+        let pushbox = FighterPushBox{dx : 24.0, dy : 16.0};
+        let mut a = FighterPosition{x : 0.0, y : 0.0, z : FLOOR_Z};
+        let mut b = FighterPosition{x : 10.0, y : 0.0, z : FLOOR_Z};
+        push_apart(&pushbox, &FacingEast(true), &mut a, &pushbox, &mut b);
+        assert!(b.x - a.x >= pushbox.dx - 1e-3);
+    }
+}
@@ -0,0 +1,531 @@
+use super::components_bundles::{FighterPosition, FighterVelocity,
+    FighterMovement, HitBox, KeyTargetSet, KeyTarget};
+use super::fighters_movement_map::*;
+use super::movement_validation::*;
+use super::{FIGHTERS, YAML_DATA};
+
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy::asset::FileAssetIo;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+//move definitions are compiled in, like the asset paths. debug builds read them from the asset root
+//instead when the files are there, so moves can be tuned without recompiling
+const MOVES_DIRPATH : &str = "assets/moves";
+const SHARED_MOVES_FILENAME : &str = "shared.yaml";
+const EMBEDDED_MOVES : [(&str, &str);3] = [
+    ("shared.yaml", include_str!("../assets/moves/shared.yaml")),
+    ("IDF.yaml", include_str!("../assets/moves/IDF.yaml")),
+    ("HAMAS.yaml", include_str!("../assets/moves/HAMAS.yaml")),
+];
+
+//ballistic flight under the fighter's gravity, shared by the airborne nodes
+fn in_air_update(pos : &mut FighterPosition, vel : &mut FighterVelocity, dt : f32, stats : &FighterStats) {
+    pos.x += vel.x*dt;
+    pos.y += vel.y*dt;
+    pos.z += vel.z*dt;
+    vel.z += stats.gravity*dt;
+}
+
+//walking along direction at the fighter's walking speed, turning to face east or west
+pub fn walk(direction : Vec2) -> StateEnterFn {
+    let direction = direction.normalize_or_zero();
+    state_enter_fn(move |_,vel,facing_east,stats| {
+        vel.x = direction.x * stats.walking_speed;
+        vel.y = direction.y * stats.walking_speed;
+        if direction.x != 0.0 {facing_east.0 = direction.x > 0.0};
+    })
+}
+
+//running along direction at the fighter's running speed, turning to face east or west
+pub fn run(direction : Vec2) -> StateEnterFn {
+    let direction = direction.normalize_or_zero();
+    state_enter_fn(move |_,vel,facing_east,stats| {
+        vel.x = direction.x * stats.running_speed;
+        vel.y = direction.y * stats.running_speed;
+        if direction.x != 0.0 {facing_east.0 = direction.x > 0.0};
+    })
+}
+
+//entered by tapping key twice, from standing or from the from movement
+pub fn double_tap(key : KeyTarget, from : FighterMovement) -> EventEnterFn {
+    event_enter_fn(move |floor_z,pos_z,fighter_movement_stack,event_keytargetset_stack,is_joined_keytargetset| {
+        if !is_joined_keytargetset {return false};
+        let window_time = 0.3;
+
+        let cond1 = pos_z == floor_z;
+        if !cond1 {return false};
+
+        //check for consecutive keypresses
+        let mut pressed = 0;
+        let mut elements = 0;
+        for timed_keyset in event_keytargetset_stack.0.stack.iter().rev() {
+            if timed_keyset.duration > window_time || pressed > 1 {break};
+            if timed_keyset.value.contains(&key) {pressed += 1};
+            elements += 1;
+        }
+        let cond2 = pressed > 1;
+        if !cond2 {return false};
+
+        //make sure last movement is idle or from
+        if let Some(last_movement) = fighter_movement_stack.last() {
+            if last_movement.value != FighterMovement::Idle && last_movement.value != from {
+                return false
+            }
+        }
+
+        //remove acted upon events from stack
+        for _ in 0..elements {
+            event_keytargetset_stack.0.pop();
+        }
+        true
+    })
+}
+
+//the node can be left for any movement but the unallowed ones
+pub fn except(unallowed_transitions : Vec<FighterMovement>) -> ExitFn {
+    exit_fn(move |_,_,_,movement_request| !unallowed_transitions.contains(movement_request))
+}
+
+//a behavior as written in a move definition, either its name or its name and parameters
+#[derive(Clone, Deserialize)]
+#[serde(untagged)]
+pub enum BehaviorDefinition {
+    Named(String),
+    Parameterized {
+        name : String,
+        #[serde(flatten)]
+        parameters : serde_yaml::Mapping,
+    },
+}
+
+//the parameters a behavior is built with
+pub struct BehaviorParameters<'a> {
+    pub name : &'a String,
+    pub values : Option<&'a serde_yaml::Mapping>,
+}
+
+impl BehaviorParameters<'_> {
+    pub fn get<T : DeserializeOwned>(&self, parameter : &str) -> Result<T, FighterMovementError> {
+        let bad_parameter = |reason : String| FighterMovementError::BadBehaviorParameter{
+            name : self.name.clone(),
+            parameter : parameter.to_string(),
+            reason : reason,
+        };
+        let value = self.values.and_then(|values| values.get(parameter))
+            .ok_or_else(|| bad_parameter("missing".to_string()))?;
+        serde_yaml::from_value(value.clone()).map_err(|err| bad_parameter(err.to_string()))
+    }
+}
+
+type BehaviorFactory<T> = Box<dyn Fn(&BehaviorParameters) -> Result<T, FighterMovementError> + Send + Sync>;
+
+//behaviors of one fn slot, by name
+pub struct BehaviorFactories<T : Clone>(HashMap<String, BehaviorFactory<T>>);
+
+impl<T : Clone + Send + Sync + 'static> BehaviorFactories<T> {
+    fn new() -> Self {
+        Self(HashMap::new())
+    }
+
+    //a behavior without parameters, shared by every node naming it
+    pub fn register(&mut self, name : &str, behavior : T) {
+        self.0.insert(name.to_string(), Box::new(move |_| Ok(behavior.clone())));
+    }
+
+    //a behavior built anew for every node, from the parameters the node gives it
+    pub fn register_parameterized(&mut self,
+                                name : &str,
+                                factory : impl Fn(&BehaviorParameters) -> Result<T, FighterMovementError> + Send + Sync + 'static) {
+        self.0.insert(name.to_string(), Box::new(factory));
+    }
+
+    fn build(&self, slot : &'static str, definition : &BehaviorDefinition) -> Result<T, FighterMovementError> {
+        let parameters = match definition {
+            BehaviorDefinition::Named(name) => BehaviorParameters{name : name, values : None},
+            BehaviorDefinition::Parameterized{name, parameters} => BehaviorParameters{name : name, values : Some(parameters)},
+        };
+        let factory = self.0.get(parameters.name)
+            .ok_or(FighterMovementError::UnknownBehavior{slot : slot, name : parameters.name.clone()})?;
+        factory(&parameters)
+    }
+}
+
+//named behaviors move definitions refer to, filling the behavior slots of the movement nodes.
+//fighters that need behaviors of their own can register them before AppState::Setup
+#[derive(Resource)]
+pub struct MovementBehaviorRegistry {
+    pub state_updates : BehaviorFactories<StateUpdateFn>,
+    pub state_enters : BehaviorFactories<StateEnterFn>,
+    pub event_enters : BehaviorFactories<EventEnterFn>,
+    pub enters : BehaviorFactories<EnterFn>,
+    pub exits : BehaviorFactories<ExitFn>,
+    pub channels : BehaviorFactories<ChannelFn>,
+}
+
+impl Default for MovementBehaviorRegistry {
+    fn default() -> Self {
+        let mut registry = Self {
+            state_updates : BehaviorFactories::new(),
+            state_enters : BehaviorFactories::new(),
+            event_enters : BehaviorFactories::new(),
+            enters : BehaviorFactories::new(),
+            exits : BehaviorFactories::new(),
+            channels : BehaviorFactories::new(),
+        };
+
+        registry.state_updates.register("none", state_update_fn(|_,_,_,_| {}));
+        registry.state_updates.register("in_air", state_update_fn(in_air_update));
+        registry.state_updates.register("move_xy", state_update_fn(|pos,vel,dt,_| {
+            pos.x += vel.x*dt;
+            pos.y += vel.y*dt;
+        }));
+        registry.state_updates.register("slide", state_update_fn(|pos,vel,dt,_| {
+            pos.x += vel.x*dt;
+            pos.y += vel.y*dt;
+            vel.x -= vel.x*FRICTION*dt;
+            vel.y -= vel.y*FRICTION*dt;
+        }));
+        registry.state_updates.register("slide_x", state_update_fn(|pos,vel,dt,_| {
+            pos.x += vel.x * dt;
+            vel.x -= vel.x * FRICTION * dt;
+        }));
+
+        registry.state_enters.register("none", state_enter_fn(|_,_,_,_| {}));
+        registry.state_enters.register("stop", state_enter_fn(|_,vel,_,_| {vel.x = 0.0; vel.y = 0.0}));
+        registry.state_enters.register("stop_all", state_enter_fn(|_,vel,_,_| {vel.x = 0.0; vel.y = 0.0; vel.z = 0.0}));
+        registry.state_enters.register_parameterized("walk", |parameters| {
+            Ok(walk(Vec2::from(parameters.get::<[f32;2]>("direction")?)))
+        });
+        registry.state_enters.register_parameterized("run", |parameters| {
+            Ok(run(Vec2::from(parameters.get::<[f32;2]>("direction")?)))
+        });
+        registry.state_enters.register("jump", state_enter_fn(|_,vel,_,stats| {vel.z = stats.jumping_speed;}));
+        registry.state_enters.register("backdash", state_enter_fn(|_,vel,facing_east,stats| {
+            vel.x = if facing_east.0 {-stats.running_speed} else {stats.running_speed};
+            vel.y = 0.0;
+        }));
+        //a rising attack, leaving the floor straight up
+        registry.state_enters.register("rise", state_enter_fn(|_,vel,_,stats| {
+            vel.x = 0.0;
+            vel.y = 0.0;
+            vel.z = stats.jumping_speed;
+        }));
+        registry.state_enters.register("charge", state_enter_fn(|_,vel,facing_east,stats| {
+            vel.x = if facing_east.0 {stats.running_speed} else {-stats.running_speed};
+            vel.y = 0.0;
+        }));
+
+        registry.enters.register("always", enter_fn(|_,_| true));
+        registry.enters.register("on_floor", enter_fn(|floor_z,z| floor_z == z));
+        registry.enters.register("in_air", enter_fn(|floor_z,z| floor_z != z));
+
+        registry.event_enters.register("on_floor", event_enter_fn(|floor_z,pos_z,_,_,_| floor_z == pos_z));
+        //only entered from the current key set, never from the joined one
+        registry.event_enters.register("on_floor_unjoined", event_enter_fn(|floor_z,pos_z,_,_,joined_keytargetset| {
+            !joined_keytargetset && floor_z == pos_z
+        }));
+        registry.event_enters.register("from_jump", event_enter_fn(|floor_z,pos_z,fighter_movement_stack,_,_| {
+            if let Some(durative_movement) = fighter_movement_stack.last() {
+                if floor_z != pos_z && durative_movement.value == FighterMovement::Jumping {
+                    return true
+                }
+            }
+            false
+        }));
+        registry.event_enters.register_parameterized("double_tap", |parameters| {
+            Ok(double_tap(parameters.get("key")?, parameters.get("from")?))
+        });
+
+        registry.exits.register("always", exit_fn(|_,_,_,_| true));
+        registry.exits.register("landed", exit_fn(|floor_z,pos_z,_,_| floor_z == pos_z));
+        registry.exits.register("jump", exit_fn(|floor_z,pos_z,_,movement_request| {
+            movement_request == &FighterMovement::JumpAttack || pos_z == floor_z
+        }));
+        registry.exits.register_parameterized("except", |parameters| {
+            Ok(except(parameters.get("movements")?))
+        });
+
+        registry.channels.register("steer_north_south", channel_fn(|full_keytargetset, vel, stats| {
+            if KeyTargetSet::from([KeyTarget::Up]).is_subset(full_keytargetset) {
+                vel.y = stats.walking_speed;
+            }
+            if KeyTargetSet::from([KeyTarget::Down]).is_subset(full_keytargetset) {
+                vel.y = -stats.walking_speed;
+            }
+        }));
+
+        registry
+    }
+}
+
+fn none_behavior() -> BehaviorDefinition {
+    BehaviorDefinition::Named("none".to_string())
+}
+
+fn always_behavior() -> BehaviorDefinition {
+    BehaviorDefinition::Named("always".to_string())
+}
+
+fn default_lane_tolerance() -> f32 {
+    DEFAULT_LANE_TOLERANCE
+}
+
+//fields shared by all node kinds. behaviors are looked up in the MovementBehaviorRegistry
+#[derive(Clone, Deserialize)]
+pub struct MovementNodeBaseDefinition {
+    pub movement : FighterMovement,
+    pub sprite_name : String,
+    #[serde(default = "none_behavior")]
+    pub state_update : BehaviorDefinition,
+    #[serde(default = "none_behavior")]
+    pub state_enter : BehaviorDefinition,
+}
+
+//one movement node as written in a move definition file, the kind picks the map it goes to
+#[derive(Clone, Deserialize)]
+#[serde(tag = "kind")]
+pub enum MovementNodeDefinition {
+    Event {
+        #[serde(flatten)]
+        base : MovementNodeBaseDefinition,
+        keys : KeyTargetSet,
+        enter : BehaviorDefinition,
+        #[serde(default = "always_behavior")]
+        exit : BehaviorDefinition,
+        #[serde(default)]
+        min_duration : f32,
+        #[serde(default)]
+        channel : Option<BehaviorDefinition>,
+        #[serde(default)]
+        duration_and_fallback : Option<DurationAndFallback>,
+        #[serde(default)]
+        hit_boxes : Vec<FramedHitBox>,
+        #[serde(default)]
+        active_frames : Option<[usize;2]>,
+        #[serde(default = "default_lane_tolerance")]
+        lane_tolerance : f32,
+        #[serde(default)]
+        hurt_boxes : Vec<FramedHitBox>,
+        #[serde(default)]
+        invunerable_frames : Option<[usize;2]>,
+        #[serde(default)]
+        armor_frames : Option<[usize;2]>,
+        #[serde(default)]
+        projectile : Option<ProjectileSpawn>,
+        #[serde(default)]
+        throw : Option<Throw>,
+        #[serde(default)]
+        meter_cost : f32,
+    },
+    Persistent {
+        #[serde(flatten)]
+        base : MovementNodeBaseDefinition,
+        keys : KeyTargetSet,
+        enter : BehaviorDefinition,
+        #[serde(default = "always_behavior")]
+        exit : BehaviorDefinition,
+        #[serde(default)]
+        hit_box : HitBox,
+        hurt_box : HitBox,
+    },
+    Uncontrollable {
+        #[serde(flatten)]
+        base : MovementNodeBaseDefinition,
+        enter : BehaviorDefinition,
+        #[serde(default)]
+        landing_fallback : Option<FighterMovement>,
+        #[serde(default)]
+        bounces : bool,
+        #[serde(default)]
+        invunerable : bool,
+        #[serde(default)]
+        armor : bool,
+        #[serde(default)]
+        hit_box : HitBox,
+        hurt_box : HitBox,
+    },
+}
+
+//moves every fighter shares
+#[derive(Clone, Deserialize)]
+pub struct MoveDefinitions {
+    pub nodes : Vec<MovementNodeDefinition>,
+}
+
+//a fighter's stats and the special moves only it has
+#[derive(Deserialize)]
+pub struct FighterDefinition {
+    pub stats : FighterStats,
+    #[serde(default)]
+    pub nodes : Vec<MovementNodeDefinition>,
+}
+
+impl MovementBehaviorRegistry {
+    fn base(&self, definition : MovementNodeBaseDefinition) -> Result<FighterMovementNodeBase, FighterMovementError> {
+        Ok(FighterMovementNodeBase {
+            movement : definition.movement,
+            sprite_name : definition.sprite_name,
+            state_update : self.state_updates.build("state_update", &definition.state_update)?,
+            state_enter : self.state_enters.build("state_enter", &definition.state_enter)?,
+        })
+    }
+
+    pub fn build_movement_map(&self,
+                            stats : FighterStats,
+                            definitions : impl IntoIterator<Item = MovementNodeDefinition>) -> Result<FighterMovementMap, FighterMovementError> {
+        let mut map = FighterMovementMap::new(stats);
+        for definition in definitions {
+            match definition {
+                MovementNodeDefinition::Event {
+                    base,
+                    keys,
+                    enter,
+                    exit,
+                    min_duration,
+                    channel,
+                    duration_and_fallback,
+                    hit_boxes,
+                    active_frames,
+                    lane_tolerance,
+                    hurt_boxes,
+                    invunerable_frames,
+                    armor_frames,
+                    projectile,
+                    throw,
+                    meter_cost } => {
+                    let channel = match channel {
+                        Some(channel) => Some(self.channels.build("channel", &channel)?),
+                        None => None,
+                    };
+                    map.insert_to_event_map(keys, EventFighterMovementNode {
+                        base : self.base(base)?,
+                        player_can_enter : self.event_enters.build("enter", &enter)?,
+                        player_can_exit : self.exits.build("exit", &exit)?,
+                        min_duration : min_duration,
+                        channel : channel,
+                        duration_and_fallback : duration_and_fallback,
+                        hit_boxes : hit_boxes,
+                        active_frames : active_frames,
+                        lane_tolerance : lane_tolerance,
+                        hurt_boxes : hurt_boxes,
+                        invunerable_frames : invunerable_frames,
+                        armor_frames : armor_frames,
+                        projectile : projectile,
+                        throw : throw,
+                        meter_cost : meter_cost,
+                    })?;
+                },
+                MovementNodeDefinition::Persistent { base, keys, enter, exit, hit_box, hurt_box } => {
+                    map.insert_to_persistent_map(keys, PersistentFighterMovementNode {
+                        base : self.base(base)?,
+                        player_can_enter : self.enters.build("enter", &enter)?,
+                        player_can_exit : self.exits.build("exit", &exit)?,
+                        hit_box : hit_box,
+                        hurt_box : hurt_box,
+                    })?;
+                },
+                MovementNodeDefinition::Uncontrollable {
+                    base,
+                    enter,
+                    landing_fallback,
+                    bounces,
+                    invunerable,
+                    armor,
+                    hit_box,
+                    hurt_box } => {
+                    map.insert_to_uncontrollable_map(UncontrollableFighterMovementNode {
+                        base : self.base(base)?,
+                        player_can_enter : self.enters.build("enter", &enter)?,
+                        landing_fallback : landing_fallback,
+                        bounces : bounces,
+                        invunerable : invunerable,
+                        armor : armor,
+                        hit_box : hit_box,
+                        hurt_box : hurt_box,
+                    })?;
+                },
+            }
+        }
+        Ok(map)
+    }
+}
+
+fn read_move_file(filename : &str) -> Result<String, FighterMovementError> {
+    let bad_definitions = |reason : String| FighterMovementError::BadMoveDefinitions{
+        filepath : filename.to_string(),
+        reason : reason,
+    };
+    if cfg!(debug_assertions) {
+        let filepath = FileAssetIo::get_base_path().join(MOVES_DIRPATH).join(filename);
+        if filepath.exists() {
+            return std::fs::read_to_string(&filepath).map_err(|err| bad_definitions(err.to_string()))
+        }
+    }
+    EMBEDDED_MOVES.iter()
+        .find(|(embedded_filename, _)| *embedded_filename == filename)
+        .map(|(_, data)| data.to_string())
+        .ok_or_else(|| bad_definitions("the move file isn't embedded in the binary".to_string()))
+}
+
+fn read_definitions<T : DeserializeOwned>(filename : &str) -> Result<T, FighterMovementError> {
+    serde_yaml::from_str(&read_move_file(filename)?).map_err(|err| FighterMovementError::BadMoveDefinitions{
+        filepath : filename.to_string(),
+        reason : err.to_string(),
+    })
+}
+
+//every fighter gets the shared moves, plus the stats and special moves of its own file.
+//the maps are validated against the sprite folders listed in assets
+pub fn build_movement_map_collection(registry : &MovementBehaviorRegistry,
+                                    assets : &serde_yaml::Mapping) -> Result<FighterMovementMapCollection, Vec<MovementGraphError>> {
+    let mut collection = FighterMovementMapCollection(HashMap::new());
+    let mut errors = Vec::new();
+    let shared = read_definitions::<MoveDefinitions>(SHARED_MOVES_FILENAME);
+    for fighter in FIGHTERS {
+        let fighter_map = shared.clone().and_then(|shared| {
+            let own : FighterDefinition = read_definitions(&format!("{}.yaml", fighter))?;
+            registry.build_movement_map(own.stats, shared.nodes.into_iter().chain(own.nodes))
+        });
+        match fighter_map {
+            Ok(fighter_map) => {
+                errors.extend(validate_movement_map(&fighter, &fighter_map, assets));
+                collection.0.insert(fighter, fighter_map);
+            },
+            Err(error) => errors.push(MovementGraphError{fighter : fighter, error : error}),
+        }
+    }
+    if errors.is_empty() {Ok(collection)} else {Err(errors)}
+}
+
+//the shipped maps without validation against the sprite folders, for tests that play moves out
+#[cfg(test)]
+pub(crate) fn shipped_movement_maps() -> FighterMovementMapCollection {
+    let registry = MovementBehaviorRegistry::default();
+    let shared : MoveDefinitions = read_definitions(SHARED_MOVES_FILENAME).unwrap();
+    FighterMovementMapCollection(FIGHTERS.into_iter().map(|fighter| {
+        let own : FighterDefinition = read_definitions(&format!("{}.yaml", fighter)).unwrap();
+        let fighter_map = registry.build_movement_map(own.stats, shared.nodes.clone().into_iter().chain(own.nodes)).unwrap();
+        (fighter, fighter_map)
+    }).collect())
+}
+
+pub(crate) fn load_movement_maps(mut commands: Commands,
+                                registry: Res<MovementBehaviorRegistry>,) {
+    let assets : serde_yaml::Mapping = serde_yaml::from_str(YAML_DATA)
+        .expect("Failed to deserialize asset paths from YAML");
+    match build_movement_map_collection(&registry, &assets) {
+        Ok(collection) => {
+            info!("movement maps loaded");
+            commands.insert_resource(collection);
+        },
+        Err(errors) => {
+            for error in errors.iter() {
+                error!("movement map of {}: {:?}", error.fighter, error.error);
+            }
+            panic!("{} errors in the movement maps, the game can't start", errors.len());
+        },
+    }
+}
@@ -0,0 +1,122 @@
+use super::components_bundles::{Fighter, FighterMovement};
+use super::fighters_movement_map::*;
+use super::movement_definitions::*;
+use super::movement_validation::key_transitions;
+use super::{FIGHTERS, YAML_DATA};
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+
+//writes the movement maps as DOT graphs into the directory after it, instead of starting the game
+pub(crate) const EXPORT_DOT_FLAG : &str = "--export-dot";
+
+fn node_color(node : &FighterMovementNode) -> &'static str {
+    match node {
+        FighterMovementNode::EventTriggered(_) => "lightblue",
+        FighterMovementNode::Persistent(_) => "palegreen",
+        FighterMovementNode::Uncontrollable(_) => "lightgrey",
+    }
+}
+
+//movements the node falls back to without the player, and when
+fn fallback_transitions(node : &FighterMovementNode) -> Vec<(String, FighterMovement)> {
+    match node {
+        FighterMovementNode::EventTriggered(node) => node.duration_and_fallback.iter()
+            .map(|x| (format!("after {}s", x.duration), x.fallback))
+            .collect(),
+        FighterMovementNode::Persistent(_) => Vec::new(),
+        FighterMovementNode::Uncontrollable(node) => node.landing_fallback.iter()
+            .map(|x| ("landed".to_string(), *x))
+            .collect(),
+    }
+}
+
+//nodes are colored by kind. solid edges are labelled with the key sets the player enters them with,
+//dashed ones are the fallbacks the node takes by itself
+pub fn movement_map_to_dot(fighter : &Fighter, fighter_map : &FighterMovementMap) -> String {
+    //sorted by name, so exports of the same map can be diffed
+    let nodes : BTreeMap<String, &FighterMovementNode> = fighter_map.movement_map.iter()
+        .map(|(movement, node)| (movement.to_string(), node))
+        .collect();
+
+    let mut dot = String::new();
+    writeln!(dot, "digraph {} {{", fighter).unwrap();
+    writeln!(dot, "    node [style=filled];").unwrap();
+    for (name, node) in nodes.iter() {
+        writeln!(dot, "    {} [fillcolor={}];", name, node_color(node)).unwrap();
+    }
+    for (name, node) in nodes.iter() {
+        let mut key_labels : BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (keys, movement) in key_transitions(fighter_map, node) {
+            let labels = key_labels.entry(movement.to_string()).or_default();
+            let label = keys.to_string();
+            if !labels.contains(&label) {labels.push(label)};
+        }
+        for (next_name, mut labels) in key_labels {
+            labels.sort();
+            writeln!(dot, "    {} -> {} [label=\"{}\"];", name, next_name, labels.join("\\n")).unwrap();
+        }
+        for (label, fallback) in fallback_transitions(node) {
+            writeln!(dot, "    {} -> {} [label=\"{}\", style=dashed];", name, fallback, label).unwrap();
+        }
+    }
+    writeln!(dot, "}}").unwrap();
+    dot
+}
+
+//one <fighter>.dot file per fighter
+pub(crate) fn export_movement_maps_dot(dirpath : &PathBuf) {
+    let registry = MovementBehaviorRegistry::default();
+    let assets : serde_yaml::Mapping = serde_yaml::from_str(YAML_DATA)
+        .expect("Failed to deserialize asset paths from YAML");
+    let collection = match build_movement_map_collection(&registry, &assets) {
+        Ok(collection) => collection,
+        Err(errors) => {
+            for error in errors.iter() {
+                eprintln!("movement map of {}: {:?}", error.fighter, error.error);
+            }
+            std::process::exit(1);
+        },
+    };
+    std::fs::create_dir_all(dirpath)
+        .unwrap_or_else(|err| panic!("Failed to create {}: {}", dirpath.display(), err));
+    for fighter in FIGHTERS {
+        let fighter_map = collection.0.get(&fighter).expect("fighter does not exist in the movement graph");
+        let filepath = dirpath.join(format!("{}.dot", fighter));
+        std::fs::write(&filepath, movement_map_to_dot(&fighter, fighter_map))
+            .unwrap_or_else(|err| panic!("Failed to write {}: {}", filepath.display(), err));
+        println!("movement map of {} written to {}", fighter, filepath.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_map_is_exported() {
+        let collection = shipped_movement_maps();
+        let dot = movement_map_to_dot(&Fighter::IDF, collection.0.get(&Fighter::IDF).unwrap());
+        assert!(dot.starts_with("digraph IDF {\n"));
+        assert!(dot.ends_with("}\n"));
+        for line in [
+            //every kind of node
+            "Fireball [fillcolor=lightblue];",
+            "WalkingEast [fillcolor=palegreen];",
+            "Juggled [fillcolor=lightgrey];",
+            //entered with keys, joined ones included
+            "Idle -> WalkingEast [label=\"Right\"];",
+            "Idle -> Fireball [label=\"DownJustPressed, AttackJustPressed\"];",
+            "Idle -> RunningEast [label=\"RightJustPressed\"];",
+            "Jumping -> JumpAttack [label=\"AttackJustPressed\"];",
+            //fallbacks of event triggered and uncontrollable nodes
+            "Fireball -> Idle [label=\"after 0.5s\", style=dashed];",
+            "Juggled -> KnockedDown [label=\"landed\", style=dashed];",
+        ] {
+            assert!(dot.contains(&format!("    {}\n", line)), "missing {}", line);
+        }
+        //jump attacks only leave the air
+        assert!(!dot.contains("Idle -> JumpAttack"));
+    }
+}
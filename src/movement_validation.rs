@@ -0,0 +1,348 @@
+use super::components_bundles::{Fighter, FighterMovementStack, FighterMovement, KeyTargetSet, KeyTargetSetStack};
+use super::fighters_movement_map::*;
+use super::FLOOR_Z;
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+//entry conditions are probed standing on the floor and this far above it
+const PROBE_HEIGHT : f32 = 1.0;
+//movements are probed as if held long enough for every exit duration to pass
+const PROBE_DURATION : f32 = f32::MAX;
+//frames of key presses probed before a node is requested
+const PROBE_HISTORY_LENGTH : usize = 3;
+
+//an error found in a fighter's movement graph
+#[derive(Debug)]
+pub struct MovementGraphError {
+    pub fighter : Fighter,
+    pub error : FighterMovementError,
+}
+
+//the state a fighter might be in when it requests a movement
+struct Probe<'a> {
+    current : &'a FighterMovementNode,
+    airborne : bool,
+}
+
+impl Probe<'_> {
+    fn pos_z(&self) -> f32 {
+        if self.airborne {FLOOR_Z + PROBE_HEIGHT} else {FLOOR_Z}
+    }
+
+    //same as can_exit_node, with the current movement held for good
+    fn can_exit(&self, request : &FighterMovement) -> bool {
+        match self.current {
+            FighterMovementNode::EventTriggered(node) => (node.player_can_exit)(FLOOR_Z, self.pos_z(), PROBE_DURATION, request),
+            FighterMovementNode::Persistent(node) => (node.player_can_exit)(FLOOR_Z, self.pos_z(), PROBE_DURATION, request),
+            FighterMovementNode::Uncontrollable(_) => true,
+        }
+    }
+
+    //the history is pushed to the key stack, oldest first. meter is full
+    fn can_enter_event(&self, history : &[KeyTargetSet], node : &EventFighterMovementNode, joined : bool) -> bool {
+        let meter = METER_SEGMENT * METER_SEGMENTS as f32;
+        let mut movement_stack = FighterMovementStack::new(10);
+        movement_stack.push(self.current.movement());
+        let mut keytargetset_stack = KeyTargetSetStack::new(10, 0.5);
+        for keys in history {
+            keytargetset_stack.0.push(keys.clone());
+        }
+        (node.player_can_enter)(FLOOR_Z, self.pos_z(), &movement_stack, &mut keytargetset_stack, joined)
+            && node.meter_cost <= meter
+            && self.can_exit(&node.base.movement)
+    }
+
+    fn can_enter_persistent(&self, node : &PersistentFighterMovementNode) -> bool {
+        node.base.movement != self.current.movement()
+            && (node.player_can_enter)(FLOOR_Z, self.pos_z())
+            && self.can_exit(&node.base.movement)
+    }
+
+    //movements entered for the keys after each key history, like player_control would
+    fn entered_event_movements(&self, keys : &KeyTargetSet, nodes : &[Arc<EventFighterMovementNode>]) -> Vec<Vec<FighterMovement>> {
+        let mut entered = Vec::new();
+        for joined in [false, true] {
+            for history in key_histories(keys, joined) {
+                entered.push(nodes.iter()
+                    .filter(|node| self.can_enter_event(&history, node, joined))
+                    .map(|node| node.base.movement)
+                    .collect());
+            }
+        }
+        entered
+    }
+
+    fn entered_persistent_movements(&self, nodes : &[Arc<PersistentFighterMovementNode>]) -> Vec<FighterMovement> {
+        if nodes.iter().any(|node| node.base.movement == self.current.movement()) {return Vec::new()};
+        nodes.iter()
+            .filter(|node| self.can_enter_persistent(node))
+            .map(|node| node.base.movement)
+            .collect()
+    }
+}
+
+//player_control looks nodes up by their exact key set, either the keys of the current frame or the keys
+//joined over the key stack. so only presses of the node's own keys can reach it, and the histories probed
+//are every sequence of up to PROBE_HISTORY_LENGTH frames pressing subsets of them, that ends in the keys
+//or joins to them. longer histories aren't probed, neither is the movement stack beyond its last movement
+fn key_histories(keys : &KeyTargetSet, joined : bool) -> Vec<Vec<KeyTargetSet>> {
+    let subsets = keys.subsets();
+    let mut histories : Vec<Vec<KeyTargetSet>> = vec![Vec::new()];
+    let mut probed = Vec::new();
+    for _ in 0..PROBE_HISTORY_LENGTH {
+        histories = histories.iter()
+            .flat_map(|history| subsets.iter().map(move |subset| {
+                let mut history = history.clone();
+                history.push(subset.clone());
+                history
+            }))
+            .collect();
+        for history in histories.iter() {
+            let reaches_node = if joined {
+                history.iter().fold(KeyTargetSet::empty(), |joined_keys, x| joined_keys + x.clone()) == *keys
+            } else {
+                history.last() == Some(keys)
+            };
+            if reaches_node {probed.push(history.clone())};
+        }
+    }
+    probed
+}
+
+fn probes(fighter_map : &FighterMovementMap) -> Vec<Probe<'_>> {
+    fighter_map.movement_map.values()
+        .flat_map(|current| [false, true].map(|airborne| Probe{current : current, airborne : airborne}))
+        .collect()
+}
+
+//the same key set entering more than one node leaves player_control no way to choose
+fn check_overlapping_entries(fighter_map : &FighterMovementMap, errors : &mut Vec<FighterMovementError>) {
+    let mut overlaps : Vec<(KeyTargetSet, bool, Vec<FighterMovement>)> = Vec::new();
+    for probe in probes(fighter_map) {
+        let mut entered = Vec::new();
+        for (keys, nodes) in fighter_map.event_map.iter() {
+            for movements in probe.entered_event_movements(keys, nodes) {
+                entered.push((keys.clone(), movements));
+            }
+        }
+        for (keys, nodes) in fighter_map.persistent_map.iter() {
+            entered.push((keys.clone(), probe.entered_persistent_movements(nodes)));
+        }
+        for (keys, movements) in entered {
+            if movements.len() < 2 {continue};
+            let overlap = (keys, probe.airborne, movements);
+            if !overlaps.contains(&overlap) {overlaps.push(overlap)};
+        }
+    }
+    for (keys, airborne, movements) in overlaps {
+        errors.push(FighterMovementError::OverlappingEntries{keys : keys, airborne : airborne, movements : movements});
+    }
+}
+
+//movements a node hands over to without the player
+fn fallbacks(node : &FighterMovementNode) -> Vec<FighterMovement> {
+    match node {
+        FighterMovementNode::EventTriggered(node) => {
+            let mut fallbacks = Vec::new();
+            if let Some(duration_and_fallback) = &node.duration_and_fallback {
+                fallbacks.push(duration_and_fallback.fallback);
+            }
+            if let Some(throw) = &node.throw {
+                fallbacks.push(throw.hold_movement);
+                fallbacks.push(throw.opponent_movement);
+            }
+            fallbacks
+        },
+        FighterMovementNode::Persistent(_) => Vec::new(),
+        FighterMovementNode::Uncontrollable(node) => node.landing_fallback.into_iter().collect(),
+    }
+}
+
+fn check_fallbacks(fighter_map : &FighterMovementMap, errors : &mut Vec<FighterMovementError>) {
+    for node in fighter_map.movement_map.values() {
+        for fallback in fallbacks(node) {
+            if !fighter_map.movement_map.contains_key(&fallback) {
+                errors.push(FighterMovementError::MissingFallback{movement : node.movement(), fallback : fallback});
+            }
+        }
+    }
+}
+
+//movements the player can request from the current node, with the keys requesting them
+pub(crate) fn key_transitions(fighter_map : &FighterMovementMap, current : &FighterMovementNode) -> Vec<(KeyTargetSet, FighterMovement)> {
+    let mut transitions = Vec::new();
+    for airborne in [false, true] {
+        let probe = Probe{current : current, airborne : airborne};
+        for (keys, nodes) in fighter_map.event_map.iter() {
+            for movement in probe.entered_event_movements(keys, nodes).concat() {
+                transitions.push((keys.clone(), movement));
+            }
+        }
+        for (keys, nodes) in fighter_map.persistent_map.iter() {
+            for movement in probe.entered_persistent_movements(nodes) {
+                transitions.push((keys.clone(), movement));
+            }
+        }
+    }
+    transitions
+}
+
+//uncontrollable nodes are entered by the game, the rest has to be reached from them
+fn check_reachability(fighter_map : &FighterMovementMap, errors : &mut Vec<FighterMovementError>) {
+    let mut reached : HashSet<FighterMovement> = fighter_map.uncontrollable_map.keys().cloned().collect();
+    let mut unvisited : Vec<FighterMovement> = reached.iter().cloned().collect();
+    while let Some(movement) = unvisited.pop() {
+        let current = fighter_map.get_node_by_movement(&movement).expect("reached movement wasn't found in fighter_map");
+        let next_movements = fallbacks(current).into_iter()
+            .chain(key_transitions(fighter_map, current).into_iter().map(|(_, movement)| movement));
+        for next_movement in next_movements {
+            if fighter_map.movement_map.contains_key(&next_movement) && reached.insert(next_movement) {
+                unvisited.push(next_movement);
+            }
+        }
+    }
+    for movement in fighter_map.movement_map.keys() {
+        if !reached.contains(movement) {
+            errors.push(FighterMovementError::UnreachableMovement(*movement));
+        }
+    }
+}
+
+//sprites are loaded from textures/<fighter>/<sprite_name>, listed in assets.yaml
+fn check_sprite_folders(fighter : &Fighter,
+                        fighter_map : &FighterMovementMap,
+                        assets : &serde_yaml::Mapping,
+                        errors : &mut Vec<FighterMovementError>) {
+    let has_folder = |sprite_name : &String| {
+        let dirpath = PathBuf::from("textures").join(fighter.to_string()).join(sprite_name);
+        dirpath.to_str().is_some_and(|key| assets.contains_key(key))
+    };
+    for node in fighter_map.movement_map.values() {
+        let mut sprite_names = vec![node.sprite_name()];
+        if let FighterMovementNode::EventTriggered(node) = node {
+            if let Some(projectile) = &node.projectile {
+                sprite_names.push(&projectile.sprite_name);
+            }
+        }
+        for sprite_name in sprite_names {
+            if !has_folder(sprite_name) {
+                errors.push(FighterMovementError::MissingSpriteFolder{movement : node.movement(), sprite_name : sprite_name.clone()});
+            }
+        }
+    }
+}
+
+//everything wrong with a fighter's movement graph, found before a match starts instead of in the middle of one
+pub fn validate_movement_map(fighter : &Fighter,
+                            fighter_map : &FighterMovementMap,
+                            assets : &serde_yaml::Mapping) -> Vec<MovementGraphError> {
+    let mut errors = Vec::new();
+    if let Err(missing_movements) = fighter_map.ensure_must_exists_movements() {
+        errors.extend(missing_movements);
+    }
+    check_fallbacks(fighter_map, &mut errors);
+    check_overlapping_entries(fighter_map, &mut errors);
+    check_reachability(fighter_map, &mut errors);
+    check_sprite_folders(fighter, fighter_map, assets, &mut errors);
+    errors.into_iter()
+        .map(|error| MovementGraphError{fighter : *fighter, error : error})
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::movement_definitions::*;
+
+    //the shipped shared moves and IDF's own, plus the nodes given
+    fn build_fighter_map(extra_nodes : &str) -> Result<FighterMovementMap, FighterMovementError> {
+        let shared : MoveDefinitions = serde_yaml::from_str(include_str!("../assets/moves/shared.yaml")).unwrap();
+        let own : FighterDefinition = serde_yaml::from_str(include_str!("../assets/moves/IDF.yaml")).unwrap();
+        let extra : MoveDefinitions = serde_yaml::from_str(extra_nodes).unwrap();
+        MovementBehaviorRegistry::default()
+            .build_movement_map(own.stats, shared.nodes.into_iter().chain(own.nodes).chain(extra.nodes))
+    }
+
+    //every sprite has a folder, so only errors of the graph itself are left
+    fn validate(extra_nodes : &str) -> Vec<FighterMovementError> {
+        let fighter_map = build_fighter_map(extra_nodes).unwrap();
+        let mut assets = serde_yaml::Mapping::new();
+        for sprite_name in fighter_map.sprite_names() {
+            assets.insert(format!("textures/IDF/{}", sprite_name).into(), serde_yaml::Value::Sequence(Vec::new()));
+        }
+        validate_movement_map(&Fighter::IDF, &fighter_map, &assets).into_iter().map(|x| x.error).collect()
+    }
+
+    #[test]
+    fn shipped_moves_are_valid() {
+        let errors = validate("nodes: []");
+        assert!(errors.is_empty(), "{:?}", errors);
+    }
+
+    #[test]
+    fn overlapping_entries_are_reported() {
+        let errors = validate("
+nodes:
+  - {kind: Event, movement: Uppercut, sprite_name: Slashing, keys: [AttackJustPressed], enter: on_floor}
+");
+        assert!(errors.iter().any(|error| matches!(error,
+            FighterMovementError::OverlappingEntries{airborne : false, movements, ..}
+                if movements.contains(&FighterMovement::Slashing) && movements.contains(&FighterMovement::Uppercut))),
+            "{:?}", errors);
+    }
+
+    #[test]
+    fn overlaps_through_key_histories_are_reported() {
+        //running east is only entered by a double tap, joined over the key stack
+        let errors = validate("
+nodes:
+  - {kind: Event, movement: Uppercut, sprite_name: Running, keys: [RightJustPressed], enter: on_floor}
+");
+        assert!(errors.iter().any(|error| matches!(error,
+            FighterMovementError::OverlappingEntries{movements, ..}
+                if movements.contains(&FighterMovement::RunningEast) && movements.contains(&FighterMovement::Uppercut))),
+            "{:?}", errors);
+    }
+
+    #[test]
+    fn unreachable_movements_are_reported() {
+        //costs more than a full meter
+        let errors = validate("
+nodes:
+  - {kind: Event, movement: Uppercut, sprite_name: Slashing, keys: [UpJustPressed], enter: on_floor, meter_cost: 1000.0}
+");
+        assert!(errors.iter().any(|error| matches!(error, FighterMovementError::UnreachableMovement(FighterMovement::Uppercut))),
+            "{:?}", errors);
+    }
+
+    #[test]
+    fn missing_fallbacks_are_reported() {
+        let errors = validate("
+nodes:
+  - {kind: Event, movement: Uppercut, sprite_name: Slashing, keys: [UpJustPressed], enter: on_floor,
+     duration_and_fallback: {duration: 0.5, fallback: ShoulderCharge}}
+");
+        assert!(errors.iter().any(|error| matches!(error,
+            FighterMovementError::MissingFallback{movement : FighterMovement::Uppercut, fallback : FighterMovement::ShoulderCharge})),
+            "{:?}", errors);
+    }
+
+    #[test]
+    fn missing_sprite_folders_are_reported() {
+        let fighter_map = build_fighter_map("nodes: []").unwrap();
+        let errors = validate_movement_map(&Fighter::IDF, &fighter_map, &serde_yaml::Mapping::new());
+        assert!(errors.iter().any(|error| matches!(&error.error,
+            FighterMovementError::MissingSpriteFolder{movement : FighterMovement::Idle, sprite_name} if sprite_name == "Idle")));
+    }
+
+    #[test]
+    fn duplicate_movements_are_reported() {
+        let result = build_fighter_map("
+nodes:
+  - {kind: Event, movement: Slashing, sprite_name: Slashing, keys: [UpJustPressed], enter: on_floor}
+");
+        assert!(matches!(result, Err(FighterMovementError::DuplicateMovement(FighterMovement::Slashing))));
+    }
+}
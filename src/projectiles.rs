@@ -0,0 +1,228 @@
+use super::components_bundles::*;
+use super::fighters_movement_map::*;
+use super::combat::*;
+use super::utils::*;
+use super::{FightersMovementAnimationIndicies, AnimationTimer,
+    WEST_WALL_X, EAST_WALL_X, NORTH_WALL_Y};
+
+use bevy::prelude::*;
+
+#[derive(Component)]
+pub struct Projectile {
+    pub owner : Entity,
+    pub fighter : Fighter, //the owner's fighter, whose texture atlas holds the projectile's sprites
+    pub sprite_name : String,
+    pub hitbox : HitBox, //relative to the projectile's position
+    pub lifetime : f32, //seconds left until the projectile vanishes
+}
+
+//world entities that fly on their own, apart from the fighters that launched them
+#[derive(Bundle)]
+pub struct ProjectileBundle {
+    pub projectile : Projectile,
+    pub hitbox : FighterHitBox,
+    pub hitstop : FighterHitstop,
+    pub position : FighterPosition,
+    pub velocity : FighterVelocity,
+    pub facing_east : FacingEast,
+    pub sprite : SpriteSheetBundle,
+}
+
+//true once the fighter's current movement launched its projectile, so it launches only once
+#[derive(Component, Default)]
+pub struct ProjectileLauncher {
+    pub launched : bool,
+}
+
+pub(crate) fn launch_projectiles(mut commands: Commands,
+                                mut query: Query<(Entity,
+                                                &Fighter,
+                                                &FighterMovementStack,
+                                                &FighterPosition,
+                                                &FacingEast,
+                                                &TextureAtlasSprite,
+                                                &mut ProjectileLauncher,)>,
+                                figher_movement_map_collection: Res<FighterMovementMapCollection>,
+                                fighters_movement_animation_indicies: Res<FightersMovementAnimationIndicies>,) {
+    for (entity,
+        fighter,
+        movement_stack,
+        position,
+        facing_east,
+        sprite,
+        mut launcher) in query.iter_mut() {
+
+        let fighter_map = figher_movement_map_collection.0.get(fighter)
+            .expect("fighter does not exist in the movement graph");
+        let Some(current_durative_movement) = movement_stack.last() else {continue};
+        let Ok(node) = fighter_map.get_event_node_by_movement(&current_durative_movement.value) else {
+            launcher.launched = false;
+            continue
+        };
+        let Some(spawn) = &node.projectile else {
+            launcher.launched = false;
+            continue
+        };
+
+        let fighter_animation_hash = fighters_movement_animation_indicies.0.get(fighter).unwrap();
+        let frame = fighter_animation_hash.animation_frame(node.sprite_name(), sprite.index);
+        if frame < spawn.frame {
+            launcher.launched = false;
+            continue
+        }
+        if launcher.launched {continue};
+        launcher.launched = true;
+
+        let sign = if facing_east.0 {1.0} else {-1.0};
+        let projectile_position = FighterPosition {
+            x : position.x + sign * spawn.offset[0],
+            y : position.y + spawn.offset[1],
+            z : position.z + spawn.offset[2],
+        };
+        let first_sprite_index = fighter_animation_hash.hashmap.get(&spawn.sprite_name)
+            .expect("projectile sprite has no animation")[0];
+        let projectile_id = commands.spawn(ProjectileBundle {
+            projectile : Projectile {
+                owner : entity,
+                fighter : *fighter,
+                sprite_name : spawn.sprite_name.clone(),
+                hitbox : spawn.hitbox,
+                lifetime : spawn.lifetime,
+            },
+            hitbox : FighterHitBox {
+                hitbox : spawn.hitbox.placed(&projectile_position, facing_east.0),
+                lane_tolerance : spawn.lane_tolerance,
+                damage : spawn.damage,
+                knockback : spawn.knockback,
+                stun : spawn.stun,
+                landed : false,
+            },
+            hitstop : FighterHitstop(0.0),
+            position : projectile_position,
+            velocity : FighterVelocity {
+                x : sign * spawn.velocity[0],
+                y : spawn.velocity[1],
+                z : spawn.velocity[2],
+            },
+            facing_east : FacingEast(facing_east.0),
+            sprite : SpriteSheetBundle {
+                texture_atlas : fighter_animation_hash.atlas_handle.clone(),
+                sprite : TextureAtlasSprite {
+                    index : first_sprite_index,
+                    flip_x : !facing_east.0,
+                    ..default()
+                },
+                ..default()
+            },
+        }).id();
+
+        commands.spawn(ShadowBundle::new(Vec2::new(10.0,5.0),
+                                        -NORTH_WALL_Y,
+                                        false,
+                                        Color::rgba(0.0, 0.0, 0.0, 0.6),
+                                        Color::rgba(0.0, 0.0, 0.0, 0.0),
+                                        1.0,
+                                        projectile_id,
+                                        -32.0
+                                    ));
+    }
+}
+
+//move projectiles and remove the ones that landed, expired or left the stage.
+//a projectile that landed freezes for the hitstop along with the defender, its owner is free to act
+pub(crate) fn update_projectiles(mut commands: Commands,
+                                mut query: Query<(Entity,
+                                                &mut Projectile,
+                                                &mut FighterHitBox,
+                                                &mut FighterHitstop,
+                                                &mut FighterPosition,
+                                                &FighterVelocity,
+                                                &FacingEast,)>,
+                                time: Res<Time>,) {
+    let dt = time.delta_seconds();
+    for (entity,
+        mut projectile,
+        mut hitbox,
+        mut hitstop,
+        mut position,
+        velocity,
+        facing_east) in query.iter_mut() {
+
+        if hitstop.0 > 0.0 {
+            hitstop.0 = (hitstop.0 - dt).max(0.0);
+            continue;
+        }
+
+        position.x += velocity.x * dt;
+        position.y += velocity.y * dt;
+        position.z += velocity.z * dt;
+        projectile.lifetime -= dt;
+
+        if hitbox.landed || projectile.lifetime <= 0.0 || position.x < WEST_WALL_X || position.x > EAST_WALL_X {
+            commands.entity(entity).despawn();
+            continue;
+        }
+        hitbox.hitbox = projectile.hitbox.placed(&position, facing_east.0);
+    }
+}
+
+//projectiles hit every fighter but their owner, and expire on impact once their hitstop is over
+pub(crate) fn resolve_projectile_hits(mut query_projectiles: Query<(&Projectile,
+                                                                &mut FighterHitBox,
+                                                                &mut FighterHitstop,
+                                                                &FighterPosition,
+                                                                &FacingEast,)>,
+                                    mut query_fighters: Query<Combatant, Without<Projectile>>,
+                                    mut hit_events: EventWriter<HitEvent>,
+                                    figher_movement_map_collection: Res<FighterMovementMapCollection>,) {
+    for (projectile,
+        mut hitbox,
+        mut hitstop,
+        position,
+        facing_east) in query_projectiles.iter_mut() {
+        for mut defender in query_fighters.iter_mut() {
+            if defender.entity == projectile.owner {continue};
+            if !try_hit(&mut hitbox, defender.hurtbox) {continue};
+
+            let defender_map = figher_movement_map_collection.0.get(defender.fighter)
+                .expect("fighter does not exist in the movement graph");
+            let (projectile_hitstop, _) = apply_hit(&hitbox,
+                    projectile.owner,
+                    position.x,
+                    facing_east.0,
+                    &mut defender,
+                    defender_map,
+                    &mut hit_events);
+            hitstop.0 = projectile_hitstop;
+            break;
+        }
+    }
+}
+
+pub(crate) fn draw_projectiles(fighters_movement_animation_indicies: Res<FightersMovementAnimationIndicies>,
+                            animation_timer: Res<AnimationTimer>,
+                            mut query: Query<(&Projectile,
+                                            &FighterHitstop,
+                                            &FighterPosition,
+                                            &mut TextureAtlasSprite,
+                                            &mut Transform,)>) {
+    for (projectile,
+        hitstop,
+        position,
+        mut sprite,
+        mut transform) in query.iter_mut() {
+
+        if animation_timer.just_finished() && hitstop.0 == 0.0 {
+            let movement_indicies = fighters_movement_animation_indicies.0.get(&projectile.fighter).unwrap()
+                                                                            .hashmap.get(&projectile.sprite_name).unwrap();
+            if sprite.index < movement_indicies[0] || sprite.index > movement_indicies[1]-1 {
+                sprite.index = movement_indicies[0];
+            } else {
+                sprite.index += 1;
+            }
+        }
+
+        let uvw = project_xyz_2_uvw(position.into());
+        transform.translation = Vec3::new(uvw[0], uvw[1], uvw[2]);
+    }
+}
@@ -0,0 +1,150 @@
+use super::components_bundles::*;
+use super::fighters_movement_map::*;
+use super::projectiles::*;
+use super::throws::*;
+use super::{AppState, NORTH_WALL_Y};
+
+use bevy::prelude::*;
+
+const ROUND_OVER_DURATION : f32 = 3.0; //seconds the result is shown before the next round
+
+//where a fighter starts every round
+#[derive(Component)]
+pub struct RoundStart {
+    pub position : [f32;3],
+    pub facing_east : bool,
+}
+
+//the winner of the round that just ended, None for a draw
+#[derive(Resource)]
+pub struct RoundResult {
+    pub winner : Option<Fighter>,
+    pub timer : Timer,
+}
+
+#[derive(Component)]
+pub struct RoundAnnouncement;
+
+//fighters out of health are knocked out, which ends the round.
+//fighters knocked out on the same tick make a draw
+pub(crate) fn check_knockouts(mut commands: Commands,
+                            mut next_state: ResMut<NextState<AppState>>,
+                            mut query: Query<(&Fighter,
+                                            &FighterHealth,
+                                            &mut FighterMovementStack,
+                                            &mut FighterPosition,
+                                            &mut FighterVelocity,
+                                            &mut FacingEast,)>,
+                            figher_movement_map_collection: Res<FighterMovementMapCollection>,) {
+    let mut standing = Vec::new();
+    let mut knocked_out = false;
+    for (fighter,
+        health,
+        mut movement_stack,
+        mut position,
+        mut velocity,
+        mut facing_east) in query.iter_mut() {
+        if health.current > 0.0 {
+            standing.push(*fighter);
+            continue;
+        }
+        knocked_out = true;
+        let fighter_map = figher_movement_map_collection.0.get(fighter)
+            .expect("fighter does not exist in the movement graph");
+        movement_stack.push(FighterMovement::KO);
+        fighter_map.get_node_by_movement(&FighterMovement::KO)
+            .expect("KO wasn't found in fighter_map")
+            .state_enter(&mut position, &mut velocity, &mut facing_east, &fighter_map.stats);
+    }
+    if !knocked_out {return};
+
+    let winner = if standing.len() == 1 {Some(standing[0])} else {None};
+    commands.insert_resource(RoundResult {
+        winner : winner,
+        timer : Timer::from_seconds(ROUND_OVER_DURATION, TimerMode::Once),
+    });
+    next_state.set(AppState::RoundOver);
+}
+
+pub(crate) fn announce_round_result(mut commands: Commands,
+                                    round_result: Res<RoundResult>,) {
+    let announcement = match round_result.winner {
+        Some(fighter) => format!("{} WINS", fighter),
+        None => "DRAW".to_string(),
+    };
+    info!("round over: {}", announcement);
+    commands.spawn((Text2dBundle {
+        text : Text::from_section(announcement, TextStyle {
+            font_size : 64.0,
+            color : Color::WHITE,
+            ..default()
+        }).with_alignment(TextAlignment::Center),
+        transform : Transform::from_translation(Vec3::new(0.0, 0.0, NORTH_WALL_Y)),
+        ..default()
+    },
+    RoundAnnouncement,));
+}
+
+//once the result was shown, clear the stage and put the fighters back at their starting positions
+pub(crate) fn update_round_over(mut commands: Commands,
+                                mut next_state: ResMut<NextState<AppState>>,
+                                mut round_result: ResMut<RoundResult>,
+                                time: Res<Time>,
+                                query_announcements: Query<Entity, With<RoundAnnouncement>>,
+                                query_projectiles: Query<Entity, With<Projectile>>,
+                                mut query_fighters: Query<(&RoundStart,
+                                                        &mut FighterHealth,
+                                                        &mut FighterGuard,
+                                                        &mut FighterStamina,
+                                                        &mut FighterCombo,
+                                                        &mut FighterJuggle,
+                                                        &mut FighterStun,
+                                                        &mut FighterHitstop,
+                                                        &mut FighterThrown,
+                                                        &mut FighterHitBox,
+                                                        &mut ProjectileLauncher,
+                                                        &mut FighterPosition,
+                                                        &mut FighterVelocity,
+                                                        &mut FacingEast,
+                                                        &mut FighterMovementStack,)>) {
+    round_result.timer.tick(time.delta());
+    if !round_result.timer.finished() {return};
+
+    for entity in query_announcements.iter().chain(query_projectiles.iter()) {
+        commands.entity(entity).despawn();
+    }
+
+    for (start,
+        mut health,
+        mut guard,
+        mut stamina,
+        mut combo,
+        mut juggle,
+        mut stun,
+        mut hitstop,
+        mut thrown,
+        mut hitbox,
+        mut launcher,
+        mut position,
+        mut velocity,
+        mut facing_east,
+        mut movement_stack) in query_fighters.iter_mut() {
+        health.current = health.max;
+        guard.current = guard.max;
+        stamina.current = stamina.max;
+        *combo = FighterCombo::default();
+        *juggle = FighterJuggle::default();
+        stun.0 = 0.0;
+        hitstop.0 = 0.0;
+        thrown.0 = None;
+        //a swing or launch cut off by the knockout must not carry into the next round
+        hitbox.landed = false;
+        *launcher = ProjectileLauncher::default();
+        *position = FighterPosition{x : start.position[0], y : start.position[1], z : start.position[2]};
+        *velocity = FighterVelocity{x : 0.0, y : 0.0, z : 0.0};
+        facing_east.0 = start.facing_east;
+        *movement_stack = FighterMovementStack::new(10);
+        movement_stack.push(FighterMovement::InAir);
+    }
+    next_state.set(AppState::InGame);
+}
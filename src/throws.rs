@@ -0,0 +1,166 @@
+use super::components_bundles::*;
+use super::fighters_movement_map::*;
+use super::combat::*;
+use super::{FightersMovementAnimationIndicies, FLOOR_Z};
+
+use bevy::prelude::*;
+use bevy::ecs::query::WorldQuery;
+
+const THROW_BREAK_STUN : f32 = 0.3;
+const THROW_BREAK_PUSHBACK : f32 = 150.0;
+
+//a throw the fighter is caught in, until it is broken or the fighter is released
+pub struct ThrowHold {
+    pub thrower : Entity,
+    pub throw : Throw,
+    pub elapsed : f32,
+    pub pressed : KeyTargetSet, //keys the held fighter pressed since it was grabbed
+}
+
+#[derive(Component, Default)]
+pub struct FighterThrown(pub Option<ThrowHold>);
+
+#[derive(WorldQuery)]
+#[world_query(mutable)]
+pub(crate) struct Grappler {
+    combatant : Combatant,
+    sprite : &'static mut TextureAtlasSprite,
+    thrown : &'static mut FighterThrown,
+}
+
+//the opponent has to stand in front of the thrower, close in both x and the y-lane
+fn in_reach(throw : &Throw, thrower : &CombatantItem, opponent : &CombatantItem) -> bool {
+    let sign = if thrower.facing_east.0 {1.0} else {-1.0};
+    let dx = sign * (opponent.position.x - thrower.position.x);
+    let dy = (opponent.position.y - thrower.position.y).abs();
+    0.0 <= dx && dx <= throw.range[0] && dy <= throw.range[1]
+}
+
+//start both paired animations from their first sprite
+fn sync_sprite(grappler : &mut GrapplerItem,
+                fighter_map : &FighterMovementMap,
+                fighters_movement_animation_indicies : &FightersMovementAnimationIndicies) {
+    let movement = grappler.combatant.movement_stack.last()
+        .expect("movement_stack is empty").value;
+    let sprite_name = fighter_map.get_node_by_movement(&movement)
+        .expect("movement wasn't found in fighter_map").sprite_name();
+    grappler.sprite.index = fighters_movement_animation_indicies.0.get(grappler.combatant.fighter).unwrap()
+        .hashmap.get(sprite_name).expect("sprite_name has no animation")[0];
+}
+
+fn try_throw(thrower : &mut GrapplerItem,
+            opponent : &mut GrapplerItem,
+            figher_movement_map_collection : &FighterMovementMapCollection,
+            fighters_movement_animation_indicies : &FightersMovementAnimationIndicies) {
+    let Some(current_durative_movement) = thrower.combatant.movement_stack.last() else {return};
+    let thrower_map = figher_movement_map_collection.0.get(thrower.combatant.fighter)
+        .expect("fighter does not exist in the movement graph");
+    let Ok(node) = thrower_map.get_event_node_by_movement(&current_durative_movement.value) else {return};
+    let Some(throw) = node.throw else {return};
+
+    if thrower.combatant.hitstop.0 > 0.0 || opponent.combatant.hitstop.0 > 0.0 {return};
+    let frame = fighters_movement_animation_indicies.0.get(thrower.combatant.fighter).unwrap()
+        .animation_frame(node.sprite_name(), thrower.sprite.index);
+    if frame < throw.frames[0] || frame > throw.frames[1] {return};
+
+    //grabs only catch grounded, vulnerable opponents that aren't reeling, docking doesn't help
+    let opponent_combatant = &opponent.combatant;
+    if opponent_combatant.position.z != FLOOR_Z || opponent_combatant.hurtbox.invunerable || opponent_combatant.stun.0 > 0.0 {
+        return
+    }
+    if !in_reach(&throw, &thrower.combatant, opponent_combatant) {return};
+
+    let opponent_map = figher_movement_map_collection.0.get(opponent.combatant.fighter)
+        .expect("fighter does not exist in the movement graph");
+    force_movement(&mut thrower.combatant, throw.hold_movement, thrower_map);
+    force_movement(&mut opponent.combatant, throw.opponent_movement, opponent_map);
+    thrower.combatant.stun.0 = throw.duration;
+    opponent.combatant.stun.0 = throw.duration;
+
+    //the opponent is held in front of the thrower, facing it
+    let sign = if thrower.combatant.facing_east.0 {1.0} else {-1.0};
+    opponent.combatant.position.x = thrower.combatant.position.x + sign * throw.hold_distance;
+    opponent.combatant.position.y = thrower.combatant.position.y;
+    opponent.combatant.facing_east.0 = !thrower.combatant.facing_east.0;
+    sync_sprite(thrower, thrower_map, fighters_movement_animation_indicies);
+    sync_sprite(opponent, opponent_map, fighters_movement_animation_indicies);
+
+    opponent.thrown.0 = Some(ThrowHold {
+        thrower : thrower.combatant.entity,
+        throw : throw,
+        elapsed : 0.0,
+        pressed : KeyTargetSet::empty(),
+    });
+    info!("{} grabbed {}", thrower.combatant.fighter, opponent.combatant.fighter);
+}
+
+//a held fighter that presses grab within the tech window breaks free, pushing both fighters apart.
+//otherwise it is released once the window passes, taking the throw's damage
+fn update_hold(thrown : &mut GrapplerItem,
+            thrower : &mut GrapplerItem,
+            figher_movement_map_collection : &FighterMovementMapCollection,
+            hit_events : &mut EventWriter<HitEvent>,
+            delta_time : f32) {
+    let Some(hold) = thrown.thrown.0.as_mut() else {return};
+    if hold.thrower != thrower.combatant.entity {return};
+    hold.elapsed += delta_time;
+    //presses are collected on the hold, the key stack is too short to span a whole tech window
+    if let Some(timed_keyset) = thrown.combatant.keytargetset_stack.0.stack.last() {
+        hold.pressed = hold.pressed.clone() + timed_keyset.value.clone();
+    }
+    let throw = hold.throw;
+    let elapsed = hold.elapsed;
+    let grab_pressed = KeyTargetSet::from(GRAB_KEYS).is_subset(&hold.pressed);
+
+    let thrown_map = figher_movement_map_collection.0.get(thrown.combatant.fighter)
+        .expect("fighter does not exist in the movement graph");
+    let thrower_map = figher_movement_map_collection.0.get(thrower.combatant.fighter)
+        .expect("fighter does not exist in the movement graph");
+
+    if elapsed <= throw.tech_window && grab_pressed {
+        thrown.thrown.0 = None;
+        let away = if thrower.combatant.facing_east.0 {1.0} else {-1.0};
+        force_movement(&mut thrown.combatant, FighterMovement::ThrowBreak, thrown_map);
+        thrown.combatant.velocity.x = away * THROW_BREAK_PUSHBACK;
+        thrown.combatant.stun.0 = THROW_BREAK_STUN;
+        force_movement(&mut thrower.combatant, FighterMovement::ThrowBreak, thrower_map);
+        thrower.combatant.velocity.x = -away * THROW_BREAK_PUSHBACK;
+        thrower.combatant.stun.0 = THROW_BREAK_STUN;
+        info!("{} broke the throw", thrown.combatant.fighter);
+        return
+    }
+
+    if elapsed > throw.tech_window {
+        thrown.thrown.0 = None;
+        let hit = FighterHitBox {
+            damage : throw.damage,
+            knockback : throw.knockback,
+            stun : throw.stun,
+            ..default()
+        };
+        let damage = deal_combo_damage(hit.damage, &mut thrown.combatant);
+        enter_hit_reaction(&hit, thrower.combatant.facing_east.0, &mut thrown.combatant, thrown_map);
+        hit_events.send(HitEvent {
+            attacker : thrower.combatant.entity,
+            defender : thrown.combatant.entity,
+            kind : HitKind::Throw,
+            damage : damage,
+            contact_point : (&*thrown.combatant.position).into(),
+        });
+        info!("{} was thrown, health left {}", thrown.combatant.fighter, thrown.combatant.health.current);
+    }
+}
+
+pub(crate) fn resolve_throws(mut query: Query<Grappler>,
+                            mut hit_events: EventWriter<HitEvent>,
+                            time: Res<Time>,
+                            figher_movement_map_collection: Res<FighterMovementMapCollection>,
+                            fighters_movement_animation_indicies: Res<FightersMovementAnimationIndicies>,) {
+    let mut combinations = query.iter_combinations_mut();
+    while let Some([mut fighter_a, mut fighter_b]) = combinations.fetch_next() {
+        try_throw(&mut fighter_a, &mut fighter_b, &figher_movement_map_collection, &fighters_movement_animation_indicies);
+        try_throw(&mut fighter_b, &mut fighter_a, &figher_movement_map_collection, &fighters_movement_animation_indicies);
+        update_hold(&mut fighter_a, &mut fighter_b, &figher_movement_map_collection, &mut hit_events, time.delta_seconds());
+        update_hold(&mut fighter_b, &mut fighter_a, &figher_movement_map_collection, &mut hit_events, time.delta_seconds());
+    }
+}